@@ -0,0 +1,395 @@
+use std::fmt;
+
+use crate::github::Asset;
+use crate::platform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os {
+    Windows,
+    Linux,
+    Mac,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    X86_64,
+    Aarch64,
+}
+
+/// The kind of PrismLauncher build to download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    /// An archive that runs from wherever it's extracted
+    Portable,
+    /// A setup executable on Windows, an AppImage on Linux
+    Installer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Host {
+    pub os: Os,
+    pub arch: Arch,
+}
+
+impl Host {
+    pub fn current() -> Self {
+        let os = match std::env::consts::OS {
+            "windows" => Os::Windows,
+            "macos" => Os::Mac,
+            _ => Os::Linux,
+        };
+        let arch = match platform::native_arch() {
+            "aarch64" => Arch::Aarch64,
+            _ => Arch::X86_64,
+        };
+
+        Self { os, arch }
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let os = match self.os {
+            Os::Windows => "Windows",
+            Os::Linux => "Linux",
+            Os::Mac => "macOS",
+        };
+        let arch = match self.arch {
+            Arch::X86_64 => "x86_64",
+            Arch::Aarch64 => "aarch64",
+        };
+        write!(f, "{} {}", os, arch)
+    }
+}
+
+/// Files attached to releases that are never builds
+const IGNORED_EXTENSIONS: [&str; 5] = [".zsync", ".sha256", ".sig", ".asc", ".txt"];
+
+fn asset_os(name: &str) -> Option<Os> {
+    if name.contains("windows") {
+        Some(Os::Windows)
+    } else if name.contains("linux") {
+        Some(Os::Linux)
+    } else if name.contains("macos") {
+        Some(Os::Mac)
+    } else {
+        None
+    }
+}
+
+fn asset_arch(name: &str) -> Option<Arch> {
+    if name.contains("arm64") || name.contains("aarch64") {
+        Some(Arch::Aarch64)
+    } else if name.contains("x86_64") || name.contains("x64") || name.contains("amd64") {
+        Some(Arch::X86_64)
+    } else {
+        None
+    }
+}
+
+/// How well an asset fits `host` and `kind`, higher is better, `None` if it can't be used at all
+pub fn score(name: &str, host: Host, kind: AssetKind) -> Option<u32> {
+    let name = name.to_lowercase();
+    if IGNORED_EXTENSIONS.iter().any(|ext| name.ends_with(ext)) || asset_os(&name)? != host.os {
+        return None;
+    }
+
+    let mut score = 100;
+    match (asset_arch(&name), host.arch) {
+        // macOS builds without an architecture in their name are universal
+        (None, _) if host.os == Os::Mac => {}
+        // Other builds without one have always been x86_64
+        (arch, host_arch) if arch.unwrap_or(Arch::X86_64) == host_arch => {}
+        // Windows on ARM runs x86_64 builds through emulation, just slower
+        (None | Some(Arch::X86_64), Arch::Aarch64) if host.os == Os::Windows => score -= 50,
+        _ => return None,
+    }
+
+    let portable = name.contains("portable");
+    match (host.os, kind) {
+        (Os::Windows, AssetKind::Portable) if portable && name.ends_with(".zip") => {}
+        (Os::Windows, AssetKind::Installer) if !portable && name.ends_with(".exe") => {}
+        (Os::Linux, AssetKind::Portable) if portable && name.ends_with(".tar.gz") => {}
+        (Os::Linux, AssetKind::Installer) if name.ends_with(".appimage") => {}
+        (Os::Mac, AssetKind::Portable) if name.ends_with(".zip") || name.ends_with(".tar.gz") => {}
+        _ => return None,
+    }
+
+    // MSVC builds are the ones upstream recommends, Qt5 builds only exist for old systems
+    if name.contains("msvc") {
+        score += 10;
+    }
+    if name.contains("qt5") || name.contains("legacy") {
+        score -= 10;
+    }
+
+    Some(score)
+}
+
+/// Picks the best asset for `host`, listing every asset that was seen if none fits
+pub fn select(assets: &[Asset], host: Host, kind: AssetKind) -> Result<&Asset, anyhow::Error> {
+    // `max_by_key` keeps the last of equally good assets, so go backwards to prefer the first
+    let best = assets
+        .iter()
+        .rev()
+        .filter_map(|asset| Some((score(&asset.name, host, kind)?, asset)))
+        .max_by_key(|(score, _)| *score);
+
+    match best {
+        Some((_, asset)) => Ok(asset),
+        None => {
+            let seen = assets
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            anyhow::bail!("no {:?} build for {} among [{}]", kind, host, seen)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIN_X64: Host = Host {
+        os: Os::Windows,
+        arch: Arch::X86_64,
+    };
+    const WIN_ARM: Host = Host {
+        os: Os::Windows,
+        arch: Arch::Aarch64,
+    };
+    const LINUX_X64: Host = Host {
+        os: Os::Linux,
+        arch: Arch::X86_64,
+    };
+    const LINUX_ARM: Host = Host {
+        os: Os::Linux,
+        arch: Arch::Aarch64,
+    };
+    const MAC_ARM: Host = Host {
+        os: Os::Mac,
+        arch: Arch::Aarch64,
+    };
+
+    /// The assets of PrismLauncher 9.4
+    const RELEASE: [&str; 14] = [
+        "PrismLauncher-9.4.tar.gz",
+        "PrismLauncher-Linux-Qt5-Portable-9.4.tar.gz",
+        "PrismLauncher-Linux-Qt6-Portable-9.4.tar.gz",
+        "PrismLauncher-Linux-aarch64.AppImage",
+        "PrismLauncher-Linux-x86_64.AppImage",
+        "PrismLauncher-Linux-x86_64.AppImage.zsync",
+        "PrismLauncher-Windows-MSVC-Portable-9.4.zip",
+        "PrismLauncher-Windows-MSVC-Setup-9.4.exe",
+        "PrismLauncher-Windows-MSVC-Setup-9.4.exe.sha256",
+        "PrismLauncher-Windows-MSVC-arm64-Portable-9.4.zip",
+        "PrismLauncher-Windows-MSVC-arm64-Setup-9.4.exe",
+        "PrismLauncher-Windows-MinGW-w64-Portable-9.4.zip",
+        "PrismLauncher-Windows-MinGW-w64-Setup-9.4.exe",
+        "PrismLauncher-macOS-9.4.zip",
+    ];
+
+    fn assets(names: &[&str]) -> Vec<Asset> {
+        names
+            .iter()
+            .map(|name| Asset {
+                name: name.to_string(),
+                browser_download_url: String::new(),
+                digest: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scores_assets() {
+        use AssetKind::*;
+        for (name, host, kind, expected) in [
+            (
+                "PrismLauncher-Windows-MSVC-Portable-9.4.zip",
+                WIN_X64,
+                Portable,
+                Some(110),
+            ),
+            (
+                "PrismLauncher-Windows-MinGW-w64-Portable-9.4.zip",
+                WIN_X64,
+                Portable,
+                Some(100),
+            ),
+            (
+                "PrismLauncher-Windows-MSVC-Setup-9.4.exe",
+                WIN_X64,
+                Portable,
+                None,
+            ),
+            (
+                "PrismLauncher-Windows-MSVC-arm64-Portable-9.4.zip",
+                WIN_X64,
+                Portable,
+                None,
+            ),
+            (
+                "PrismLauncher-Windows-MSVC-Setup-9.4.exe",
+                WIN_X64,
+                Installer,
+                Some(110),
+            ),
+            (
+                "PrismLauncher-Windows-MSVC-Portable-9.4.zip",
+                WIN_X64,
+                Installer,
+                None,
+            ),
+            (
+                "PrismLauncher-Windows-MSVC-Setup-9.4.exe.sha256",
+                WIN_X64,
+                Installer,
+                None,
+            ),
+            (
+                "PrismLauncher-Windows-MSVC-arm64-Setup-9.4.exe",
+                WIN_ARM,
+                Installer,
+                Some(110),
+            ),
+            (
+                "PrismLauncher-Windows-MSVC-Setup-9.4.exe",
+                WIN_ARM,
+                Installer,
+                Some(60),
+            ),
+            (
+                "PrismLauncher-Linux-x86_64.AppImage",
+                LINUX_X64,
+                Installer,
+                Some(100),
+            ),
+            (
+                "PrismLauncher-Linux-x86_64.AppImage.zsync",
+                LINUX_X64,
+                Installer,
+                None,
+            ),
+            (
+                "PrismLauncher-Linux-aarch64.AppImage",
+                LINUX_X64,
+                Installer,
+                None,
+            ),
+            (
+                "PrismLauncher-Linux-aarch64.AppImage",
+                LINUX_ARM,
+                Installer,
+                Some(100),
+            ),
+            (
+                "PrismLauncher-Linux-x86_64.AppImage",
+                LINUX_ARM,
+                Installer,
+                None,
+            ),
+            (
+                "PrismLauncher-Linux-Qt6-Portable-9.4.tar.gz",
+                LINUX_X64,
+                Portable,
+                Some(100),
+            ),
+            (
+                "PrismLauncher-Linux-Qt5-Portable-9.4.tar.gz",
+                LINUX_X64,
+                Portable,
+                Some(90),
+            ),
+            (
+                "PrismLauncher-Linux-Qt6-Portable-9.4.tar.gz",
+                LINUX_ARM,
+                Portable,
+                None,
+            ),
+            ("PrismLauncher-9.4.tar.gz", LINUX_X64, Portable, None),
+            ("PrismLauncher-macOS-9.4.zip", MAC_ARM, Portable, Some(100)),
+            ("PrismLauncher-macOS-9.4.zip", MAC_ARM, Installer, None),
+            (
+                "PrismLauncher-Linux-x86_64.AppImage",
+                MAC_ARM,
+                Installer,
+                None,
+            ),
+        ] {
+            assert_eq!(
+                score(name, host, kind),
+                expected,
+                "{} on {} as {:?}",
+                name,
+                host,
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn selects_best_asset_of_a_release() {
+        use AssetKind::*;
+        let release = assets(&RELEASE);
+        for (host, kind, expected) in [
+            (
+                WIN_X64,
+                Portable,
+                "PrismLauncher-Windows-MSVC-Portable-9.4.zip",
+            ),
+            (
+                WIN_X64,
+                Installer,
+                "PrismLauncher-Windows-MSVC-Setup-9.4.exe",
+            ),
+            (
+                WIN_ARM,
+                Portable,
+                "PrismLauncher-Windows-MSVC-arm64-Portable-9.4.zip",
+            ),
+            (
+                WIN_ARM,
+                Installer,
+                "PrismLauncher-Windows-MSVC-arm64-Setup-9.4.exe",
+            ),
+            (
+                LINUX_X64,
+                Portable,
+                "PrismLauncher-Linux-Qt6-Portable-9.4.tar.gz",
+            ),
+            (LINUX_X64, Installer, "PrismLauncher-Linux-x86_64.AppImage"),
+            (LINUX_ARM, Installer, "PrismLauncher-Linux-aarch64.AppImage"),
+            (MAC_ARM, Portable, "PrismLauncher-macOS-9.4.zip"),
+        ] {
+            let asset = select(&release, host, kind).unwrap();
+            assert_eq!(asset.name, expected, "{} as {:?}", host, kind);
+        }
+    }
+
+    #[test]
+    fn prefers_the_first_of_equally_good_assets() {
+        let release = assets(&[
+            "PrismLauncher-Linux-a.AppImage",
+            "PrismLauncher-Linux-b.AppImage",
+        ]);
+        let asset = select(&release, LINUX_X64, AssetKind::Installer).unwrap();
+        assert_eq!(asset.name, "PrismLauncher-Linux-a.AppImage");
+    }
+
+    #[test]
+    fn lists_seen_assets_if_nothing_fits() {
+        let release = assets(&[
+            "PrismLauncher-Linux-x86_64.AppImage",
+            "PrismLauncher-9.4.tar.gz",
+        ]);
+        let err = select(&release, WIN_X64, AssetKind::Installer).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no Installer build for Windows x86_64 among \
+             [PrismLauncher-Linux-x86_64.AppImage, PrismLauncher-9.4.tar.gz]"
+        );
+        assert!(select(&[], LINUX_ARM, AssetKind::Portable).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+
+use modpack_installer_lib::metadata::{self, MetadataError};
+use modpack_installer_lib::ModpackConfig;
+
+const USAGE: &str = "Usage: embed-metadata <command> <binary> [config.json]
+
+Commands:
+  embed <binary> <config.json>    Embed the config, fails if the binary already has one
+  replace <binary> <config.json>  Replace the embedded config, or embed it if there is none
+  strip <binary>                  Remove all embedded configs
+  print <binary>                  Print the embedded config";
+
+/// Parses the config so we never embed something the installer can't read
+fn load_config(path: &Path) -> Result<Vec<u8>, anyhow::Error> {
+    let raw = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read config {}: {}", path.display(), e))?;
+
+    let config: ModpackConfig = serde_json::from_str(&raw)
+        .map_err(|e| anyhow::anyhow!("Invalid modpack config {}: {}", path.display(), e))?;
+
+    Ok(serde_json::to_vec(&config)?)
+}
+
+fn open_binary(path: &Path, write: bool) -> Result<File, anyhow::Error> {
+    OpenOptions::new()
+        .read(true)
+        .write(write)
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path.display(), e))
+}
+
+fn embed(binary: &Path, config: &Path, replace: bool) -> Result<(), anyhow::Error> {
+    let payload = load_config(config)?;
+    let mut file = open_binary(binary, true)?;
+
+    if replace {
+        let stripped = metadata::replace_trailer(&mut file, &payload)?;
+        println!("Removed {} existing config(s)", stripped);
+    } else {
+        match metadata::find_trailer(&mut file) {
+            Err(MetadataError::NotEmbedded) => {}
+            Ok(_) => anyhow::bail!(
+                "{} already has an embedded config, use `replace` instead",
+                binary.display()
+            ),
+            Err(e) => anyhow::bail!("{}, use `replace` to overwrite it", e),
+        }
+        metadata::append_trailer(&mut file, &payload)?;
+    }
+
+    println!(
+        "Embedded {} bytes of metadata into {}",
+        payload.len(),
+        binary.display()
+    );
+
+    Ok(())
+}
+
+fn strip(binary: &Path) -> Result<(), anyhow::Error> {
+    let mut file = open_binary(binary, true)?;
+    let stripped = metadata::strip_trailers(&mut file)?;
+    println!("Removed {} config(s) from {}", stripped, binary.display());
+
+    Ok(())
+}
+
+fn print(binary: &Path) -> Result<(), anyhow::Error> {
+    let mut file = open_binary(binary, false)?;
+    let trailer = metadata::find_trailer(&mut file)?;
+
+    let config: ModpackConfig = serde_json::from_slice(&trailer.payload)
+        .map_err(|e| anyhow::anyhow!("Embedded config is not a valid modpack config: {}", e))?;
+
+    println!("{}", serde_json::to_string_pretty(&config)?);
+    if trailer.version == 0 {
+        eprintln!(
+            "Note: config uses the legacy layout without a checksum, run `replace` to upgrade it"
+        );
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+
+    match args.as_slice() {
+        ["embed", binary, config] => embed(Path::new(binary), Path::new(config), false),
+        ["replace", binary, config] => embed(Path::new(binary), Path::new(config), true),
+        ["strip", binary] => strip(Path::new(binary)),
+        ["print", binary] => print(Path::new(binary)),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use async_stream::try_stream;
+use futures_core::Stream;
+use futures_util::{pin_mut, StreamExt};
+use reqwest::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::time::timeout;
+
+use crate::download::{
+    download, download_with_validators, verify_sha256, DownloadEvent, DownloadPolicy,
+    DownloadProgress, Validators,
+};
+use crate::util::sha256_file;
+
+/// Least recently used downloads are evicted once the cache grows beyond this
+const MAX_CACHE_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+/// What the cache knows about a downloaded file, stored next to it as `<key>.json`
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    sha256: String,
+    size: u64,
+    /// Seconds since the unix epoch
+    last_used: u64,
+}
+
+fn key(url: &str) -> String {
+    hex::encode(Sha256::digest(url.as_bytes()))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn read_entry(dir: &Path, key: &str) -> Option<Entry> {
+    let entry: Entry =
+        serde_json::from_slice(&std::fs::read(dir.join(format!("{}.json", key))).ok()?).ok()?;
+    let size = std::fs::metadata(dir.join(key)).ok()?.len();
+    (size == entry.size).then_some(entry)
+}
+
+fn write_entry(dir: &Path, key: &str, entry: &Entry) -> Result<(), anyhow::Error> {
+    std::fs::write(
+        dir.join(format!("{}.json", key)),
+        serde_json::to_vec(entry)?,
+    )?;
+    Ok(())
+}
+
+/// Whether a download of `url` is cached, so it can be used even if the server is unreachable
+pub fn is_cached(cache_dir: Option<&Path>, url: &str) -> bool {
+    cache_dir.is_some_and(|dir| read_entry(dir, &key(url)).is_some())
+}
+
+/// Whether the server still serves the cached version, `None` if it couldn't be reached
+async fn revalidate(url: &str, entry: &Entry, policy: &DownloadPolicy) -> Option<bool> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(policy.stall_timeout)
+        .build()
+        .ok()?;
+    let mut req = client.get(url);
+    if let Some(etag) = &entry.etag {
+        req = req.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &entry.last_modified {
+        req = req.header(IF_MODIFIED_SINCE, last_modified);
+    }
+
+    // Only the status is needed, the body is dropped with the response
+    match timeout(policy.stall_timeout, req.send()).await {
+        Ok(Ok(res)) => Some(res.status() == StatusCode::NOT_MODIFIED),
+        Ok(Err(e)) => {
+            log::warn!("Failed to check {} for changes: {}", url, e);
+            None
+        }
+        Err(_) => {
+            log::warn!("Timed out checking {} for changes", url);
+            None
+        }
+    }
+}
+
+/// Like `download`, but reuses a previous download of `url` if the server says it didn't change
+///
+/// Files matching `expected_sha256` are used without asking the server at all, and a cached file
+/// is also used if the server can't be reached, which makes primed installs work offline. With
+/// `expected_sha256` set, downloads that don't match it fail and are never cached. Without a
+/// `cache_dir` this is a plain download.
+pub fn fetch(
+    cache_dir: Option<&Path>,
+    url: &str,
+    dest: &Path,
+    policy: &DownloadPolicy,
+    expected_sha256: Option<&str>,
+) -> impl Stream<Item = Result<DownloadProgress, anyhow::Error>> {
+    let cache_dir = cache_dir.map(Path::to_path_buf);
+    let url = url.to_owned();
+    let dest = dest.to_owned();
+    let policy = *policy;
+    let expected_sha256 = expected_sha256.map(|hash| hash.trim().to_lowercase());
+    try_stream! {
+        let Some(dir) = cache_dir else {
+            log::warn!("No cache directory available, downloading {} without caching", url);
+            let download = download(&url, &dest, &policy);
+            pin_mut!(download);
+            while let Some(progress) = download.next().await {
+                yield progress?;
+            }
+            if let Some(expected) = &expected_sha256 {
+                verify_sha256(&dest, expected, &url)?;
+            }
+            return;
+        };
+
+        let key = key(&url);
+        let mut entry = read_entry(&dir, &key);
+        if let (Some(cached), Some(expected)) = (&entry, &expected_sha256) {
+            if cached.sha256 != *expected {
+                log::info!("Cached {} doesn't have the expected sha256, evicting it", url);
+                remove_entry(&dir, &key);
+                entry = None;
+            }
+        }
+
+        // A file with a known hash can't have changed, everything else is checked with the server
+        if let Some(cached) = entry.as_ref().filter(|_| expected_sha256.is_none()) {
+            match revalidate(&url, cached, &policy).await {
+                Some(true) => {}
+                Some(false) => entry = None,
+                None => log::warn!("Using cached {} without checking for changes", url),
+            }
+        }
+
+        if let Some(mut entry) = entry {
+            tokio::fs::copy(dir.join(&key), &dest).await?;
+            // Catches cache files that got corrupted on disk since they were stored
+            let intact = expected_sha256
+                .as_deref()
+                .is_none_or(|expected| verify_sha256(&dest, expected, &url).is_ok());
+            if intact {
+                log::info!("Using cached download of {}", url);
+                entry.last_used = now();
+                if let Err(e) = write_entry(&dir, &key, &entry) {
+                    log::warn!("Failed to update cache entry of {}: {}", url, e);
+                }
+
+                yield DownloadProgress { downloaded: entry.size, total: Some(entry.size), retry: None };
+                return;
+            }
+
+            log::warn!("Cached download of {} is corrupt, downloading it again", url);
+            remove_entry(&dir, &key);
+        }
+
+        // The validators come from the response the file is downloaded from, so there's no need
+        // for a separate request to get them
+        let download = download_with_validators(&url, &dest, &policy);
+        pin_mut!(download);
+        let mut validators = Validators::default();
+        while let Some(event) = download.next().await {
+            match event? {
+                DownloadEvent::Progress(progress) => yield progress,
+                DownloadEvent::Validators(v) => validators = v,
+            }
+        }
+
+        // Caching a corrupt download would hand it out again on every later install
+        if let Some(expected) = &expected_sha256 {
+            verify_sha256(&dest, expected, &url)?;
+        }
+        if let Err(e) = store(&dir, &key, &url, &dest, validators) {
+            log::warn!("Failed to cache download of {}: {}", url, e);
+        }
+    }
+}
+
+/// Copies a finished download into the cache and evicts old entries to stay within its size
+fn store(
+    dir: &Path,
+    key: &str,
+    url: &str,
+    file: &Path,
+    validators: Validators,
+) -> Result<(), anyhow::Error> {
+    std::fs::create_dir_all(dir)?;
+    let size = std::fs::copy(file, dir.join(key))?;
+    let entry = Entry {
+        url: url.to_string(),
+        etag: validators.etag,
+        last_modified: validators.last_modified,
+        sha256: sha256_file(file)?,
+        size,
+        last_used: now(),
+    };
+    write_entry(dir, key, &entry)?;
+
+    evict(dir, MAX_CACHE_SIZE)
+}
+
+fn remove_entry(dir: &Path, key: &str) {
+    let _ = std::fs::remove_file(dir.join(key));
+    let _ = std::fs::remove_file(dir.join(format!("{}.json", key)));
+}
+
+/// Removes least recently used entries until the cache is at most `max_size` bytes
+fn evict(dir: &Path, max_size: u64) -> Result<(), anyhow::Error> {
+    let mut entries = Vec::new();
+    for file in std::fs::read_dir(dir)? {
+        let path = file?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            continue;
+        }
+
+        let Some(key) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        match read_entry(dir, &key) {
+            Some(entry) => entries.push((key, entry.size, entry.last_used)),
+            // Leftovers of an interrupted store, they'd never be used
+            None => remove_entry(dir, &key),
+        }
+    }
+
+    let mut size = entries.iter().map(|(_, size, _)| size).sum::<u64>();
+    entries.sort_by_key(|(_, _, last_used)| *last_used);
+    for (key, entry_size, _) in entries {
+        if size <= max_size {
+            break;
+        }
+
+        log::info!("Evicting {} from the download cache", key);
+        std::fs::remove_file(dir.join(&key))?;
+        let _ = std::fs::remove_file(dir.join(format!("{}.json", key)));
+        size -= entry_size;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deletion_guard::TemporaryFileCleaner;
+    use crate::test_util::{self, Server};
+
+    fn fetch_to(
+        cache_dir: &Path,
+        url: &str,
+        expected_sha256: Option<&str>,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        let dest = TemporaryFileCleaner::new();
+        let dest = dest.file_path();
+        tauri::async_runtime::block_on(async {
+            let fetch = fetch(
+                Some(cache_dir),
+                url,
+                dest,
+                &DownloadPolicy::default(),
+                expected_sha256,
+            );
+            pin_mut!(fetch);
+            while let Some(progress) = fetch.next().await {
+                progress?;
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
+        Ok(std::fs::read(dest)?)
+    }
+
+    fn cached(cache_dir: &Path, url: &str) -> Option<Entry> {
+        read_entry(cache_dir, &key(url))
+    }
+
+    fn sha256(data: &[u8]) -> String {
+        hex::encode(Sha256::digest(data))
+    }
+
+    #[test]
+    fn uncached_files_are_requested_once() {
+        let tmp = test_util::temp_dir();
+        let dir = tmp.file_path();
+        let server = Server::new(&[("/file", b"first")]);
+        let url = server.url("/file");
+
+        assert_eq!(fetch_to(dir, &url, None).unwrap(), b"first");
+        assert_eq!(server.hits("/file"), 1);
+        let entry = cached(dir, &url).unwrap();
+        assert_eq!(entry.etag, Some(format!("\"{}\"", sha256(b"first"))));
+        assert_eq!(entry.sha256, sha256(b"first"));
+    }
+
+    #[test]
+    fn cached_files_are_revalidated() {
+        let tmp = test_util::temp_dir();
+        let dir = tmp.file_path();
+        let server = Server::new(&[("/file", b"first")]);
+        let url = server.url("/file");
+        fetch_to(dir, &url, None).unwrap();
+
+        // Unchanged: a single conditional request answered with 304
+        assert_eq!(fetch_to(dir, &url, None).unwrap(), b"first");
+        assert_eq!(server.hits("/file"), 2);
+
+        // Changed: the check and the download itself
+        server.set("/file", b"second");
+        assert_eq!(fetch_to(dir, &url, None).unwrap(), b"second");
+        assert_eq!(server.hits("/file"), 4);
+        assert_eq!(cached(dir, &url).unwrap().sha256, sha256(b"second"));
+    }
+
+    #[test]
+    fn known_hashes_skip_the_server() {
+        let tmp = test_util::temp_dir();
+        let dir = tmp.file_path();
+        let server = Server::new(&[("/file", b"first")]);
+        let url = server.url("/file");
+        fetch_to(dir, &url, None).unwrap();
+
+        let expected = sha256(b"first");
+        assert_eq!(fetch_to(dir, &url, Some(&expected)).unwrap(), b"first");
+        assert_eq!(server.hits("/file"), 1);
+    }
+
+    #[test]
+    fn entries_with_another_hash_are_evicted() {
+        let tmp = test_util::temp_dir();
+        let dir = tmp.file_path();
+        let server = Server::new(&[("/file", b"first")]);
+        let url = server.url("/file");
+        fetch_to(dir, &url, None).unwrap();
+
+        // The server still has the old file, which must neither be used nor cached again
+        let expected = sha256(b"second");
+        assert!(fetch_to(dir, &url, Some(&expected)).is_err());
+        assert!(cached(dir, &url).is_none());
+
+        server.set("/file", b"second");
+        assert_eq!(fetch_to(dir, &url, Some(&expected)).unwrap(), b"second");
+        assert_eq!(cached(dir, &url).unwrap().sha256, expected);
+    }
+
+    #[test]
+    fn corrupt_cache_files_are_downloaded_again() {
+        let tmp = test_util::temp_dir();
+        let dir = tmp.file_path();
+        let server = Server::new(&[("/file", b"first")]);
+        let url = server.url("/file");
+        fetch_to(dir, &url, None).unwrap();
+        std::fs::write(dir.join(key(&url)), b"fir5t").unwrap();
+
+        assert_eq!(
+            fetch_to(dir, &url, Some(&sha256(b"first"))).unwrap(),
+            b"first"
+        );
+        assert_eq!(server.hits("/file"), 2);
+        assert_eq!(std::fs::read(dir.join(key(&url))).unwrap(), b"first");
+    }
+
+    #[test]
+    fn evicts_least_recently_used_entries() {
+        let tmp = test_util::temp_dir();
+        let dir = tmp.file_path();
+        for (i, last_used) in [(0, 30), (1, 10), (2, 20)] {
+            let key = format!("entry{}", i);
+            std::fs::write(dir.join(&key), [0; 10]).unwrap();
+            let entry = Entry {
+                url: key.clone(),
+                etag: None,
+                last_modified: None,
+                sha256: String::new(),
+                size: 10,
+                last_used,
+            };
+            write_entry(dir, &key, &entry).unwrap();
+        }
+        std::fs::write(dir.join("leftover"), b"interrupted").unwrap();
+
+        evict(dir, 20).unwrap();
+        let mut left = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        left.sort();
+        assert_eq!(left, ["entry0", "entry0.json", "entry2", "entry2.json"]);
+    }
+}
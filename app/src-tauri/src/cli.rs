@@ -0,0 +1,574 @@
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::download::DownloadPolicy;
+use crate::installer;
+use crate::modpack::InstallOptions;
+use crate::platform;
+use crate::progress::StdoutProgress;
+use crate::verify::{InstanceReport, IssueKind};
+
+const USAGE: &str = "Usage: modpack-installer --headless [options]
+       modpack-installer --uninstall [--portable <dir> | --launcher-path <exe>] [--backup-saves <dir>]
+       modpack-installer --verify | --repair [<kinds>] [--portable <dir> | --launcher-path <exe>]
+       modpack-installer --sync [--portable <dir> | --launcher-path <exe>]
+
+Installs, uninstalls, verifies, repairs or syncs the embedded modpack without opening a window.
+
+Options:
+  --portable <dir>          Download a portable PrismLauncher into <dir> and install there
+  --launcher-path <exe>     Use this PrismLauncher executable instead of auto-detecting it
+  --no-launch               Don't start PrismLauncher after installing
+  --new-instance            Always create a new instance instead of updating a previous install
+  --retries <n>             Resume failed downloads up to <n> times (default 5)
+  --stall-timeout <secs>    Retry downloads that received no data for <secs> seconds (default 30)
+  --uninstall               Remove the modpack's instances, icons and shortcuts instead of installing
+  --backup-saves <dir>      Zip the saves of removed instances into <dir> before uninstalling
+  --verify                  Check installed instances for broken settings, files and shortcuts
+  --repair [<kinds>]        Ask which issues --verify reports to fix, or fix the comma-separated
+                            kinds given (missing_bootstrap, modified_bootstrap, pre_launch_command,
+                            override_commands, missing_icon, missing_shortcut, broken_shortcut or all)
+  --sync                    Download the pack's current mods and files without starting Java
+  -h, --help                Show this help";
+
+#[derive(Debug, Default)]
+pub struct HeadlessArgs {
+    pub portable: Option<PathBuf>,
+    pub launcher_path: Option<PathBuf>,
+    pub no_launch: bool,
+    pub new_instance: bool,
+    pub download: DownloadPolicy,
+    pub uninstall: bool,
+    pub backup_saves: Option<PathBuf>,
+    pub verify: bool,
+    pub repair: bool,
+    /// Issue kinds to fix without asking, `None` asks about every issue
+    pub repair_kinds: Option<Vec<IssueKind>>,
+    pub sync: bool,
+}
+
+/// What the installer was asked to do on the command line
+#[derive(Debug)]
+pub enum Command {
+    /// Start the installer window, ignoring arguments the OS or a shell wrapper passed along
+    Window {
+        ignored: Vec<String>,
+    },
+    /// Print the usage
+    Help,
+    Headless(HeadlessArgs),
+}
+
+/// Parses the command line
+pub fn parse_args(args: Vec<String>) -> Result<Command, String> {
+    if args.is_empty() {
+        return Ok(Command::Window {
+            ignored: Vec::new(),
+        });
+    }
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        return Ok(Command::Help);
+    }
+
+    let mut headless = false;
+    let mut options = false;
+    let mut unknown = Vec::new();
+    let mut args = args.into_iter().peekable();
+    let mut parsed = HeadlessArgs::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => headless = true,
+            "--portable" => {
+                let dir = args.next().ok_or("--portable requires a directory")?;
+                parsed.portable = Some(PathBuf::from(dir));
+            }
+            "--launcher-path" => {
+                let exe = args
+                    .next()
+                    .ok_or("--launcher-path requires an executable")?;
+                parsed.launcher_path = Some(PathBuf::from(exe));
+            }
+            "--no-launch" => parsed.no_launch = true,
+            "--new-instance" => parsed.new_instance = true,
+            "--retries" => {
+                let n = args.next().ok_or("--retries requires a number")?;
+                parsed.download.retries = n
+                    .parse()
+                    .map_err(|_| format!("Invalid number of retries: {}", n))?;
+            }
+            "--stall-timeout" => {
+                let secs = args
+                    .next()
+                    .ok_or("--stall-timeout requires a number of seconds")?;
+                let secs = secs
+                    .parse()
+                    .ok()
+                    .filter(|secs| *secs > 0)
+                    .ok_or_else(|| format!("Invalid stall timeout: {}", secs))?;
+                parsed.download.stall_timeout = Duration::from_secs(secs);
+            }
+            "--uninstall" => parsed.uninstall = true,
+            "--verify" => parsed.verify = true,
+            "--repair" => {
+                parsed.repair = true;
+                if let Some(kinds) = args.next_if(|a| !a.starts_with('-')) {
+                    parsed.repair_kinds = Some(parse_kinds(&kinds)?);
+                }
+            }
+            "--sync" => parsed.sync = true,
+            "--backup-saves" => {
+                let dir = args.next().ok_or("--backup-saves requires a directory")?;
+                parsed.backup_saves = Some(PathBuf::from(dir));
+            }
+            _ => {
+                unknown.push(arg);
+                continue;
+            }
+        }
+        options = true;
+    }
+
+    let mode = parsed.uninstall || parsed.verify || parsed.repair || parsed.sync;
+    if let Some(arg) = unknown.first().filter(|_| headless || mode) {
+        return Err(format!("Unknown argument: {}\n\n{}", arg, USAGE));
+    }
+    // macOS passes `-psn_*` and Explorer may pass a path, neither should keep the window closed
+    if !headless && !mode && !options {
+        return Ok(Command::Window { ignored: unknown });
+    }
+    if !headless && !mode {
+        return Err(format!(
+            "Options need --headless, --uninstall, --verify, --repair or --sync\n\n{}",
+            USAGE
+        ));
+    }
+
+    if parsed.portable.is_some() && parsed.launcher_path.is_some() {
+        return Err("--portable and --launcher-path can't be used together".to_string());
+    }
+
+    if [parsed.uninstall, parsed.verify, parsed.repair, parsed.sync]
+        .iter()
+        .filter(|a| **a)
+        .count()
+        > 1
+    {
+        return Err(
+            "--uninstall, --verify, --repair and --sync can't be used together".to_string(),
+        );
+    }
+
+    if parsed.backup_saves.is_some() && !parsed.uninstall {
+        return Err("--backup-saves can only be used with --uninstall".to_string());
+    }
+
+    Ok(Command::Headless(parsed))
+}
+
+/// Parses a comma-separated list of issue kinds like `missing_icon,broken_shortcut`
+fn parse_kinds(list: &str) -> Result<Vec<IssueKind>, String> {
+    if list == "all" {
+        return Ok(IssueKind::ALL.to_vec());
+    }
+    list.split(',')
+        .map(|kind| {
+            serde_json::from_value(serde_json::Value::String(kind.trim().to_string()))
+                .map_err(|_| format!("Unknown issue kind: {}\n\n{}", kind, USAGE))
+        })
+        .collect()
+}
+
+/// Handles the command line, returns the process exit code unless the window should start
+pub fn run() -> Option<i32> {
+    let args = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(Command::Headless(args)) => {
+            platform::attach_console();
+            args
+        }
+        Ok(Command::Help) => {
+            platform::attach_console();
+            println!("{}", USAGE);
+            return Some(0);
+        }
+        Ok(Command::Window { .. }) => return None,
+        Err(e) => {
+            platform::attach_console();
+            eprintln!("{}", e);
+            return Some(2);
+        }
+    };
+
+    if args.uninstall {
+        return Some(uninstall(&args));
+    }
+    if args.verify || args.repair {
+        return Some(verify(&args));
+    }
+    if args.sync {
+        return Some(sync(&args));
+    }
+
+    let options = InstallOptions {
+        launch: !args.no_launch,
+        update_existing: !args.new_instance,
+        download: args.download,
+        ..Default::default()
+    };
+    let res = tauri::async_runtime::block_on(async move {
+        match args.portable {
+            Some(dir) => installer::install_portable(&dir, options, &StdoutProgress).await,
+            None => {
+                installer::use_or_install_launcher(args.launcher_path, options, &StdoutProgress)
+                    .await
+            }
+        }
+    });
+
+    match res {
+        Ok(()) => {
+            println!("Installation completed successfully");
+            Some(0)
+        }
+        Err(e) => {
+            eprintln!("Installation failed: {}", e);
+            Some(1)
+        }
+    }
+}
+
+fn uninstall(args: &HeadlessArgs) -> i32 {
+    let res = installer::uninstall(
+        args.portable.as_deref(),
+        args.launcher_path.as_deref(),
+        args.backup_saves.as_deref(),
+    );
+
+    match res {
+        Ok(report) => {
+            for backup in &report.backups {
+                println!("Backed up saves to {}", backup.display());
+            }
+            for instance in &report.instances {
+                println!("Removed instance {}", instance.display());
+            }
+            println!("Uninstalled successfully");
+            0
+        }
+        Err(e) => {
+            eprintln!("Uninstall failed: {}", e);
+            1
+        }
+    }
+}
+
+fn verify(args: &HeadlessArgs) -> i32 {
+    let portable = args.portable.as_deref();
+    let launcher_path = args.launcher_path.as_deref();
+    let res = match (args.repair, &args.repair_kinds) {
+        (true, Some(kinds)) => tauri::async_runtime::block_on(installer::repair(
+            portable,
+            launcher_path,
+            None,
+            Some(kinds),
+        )),
+        (true, None) => repair_interactively(args),
+        (false, _) => installer::verify(portable, launcher_path),
+    };
+
+    let reports = match res {
+        Ok(reports) => reports,
+        Err(e) => {
+            eprintln!("Verification failed: {}", e);
+            return 1;
+        }
+    };
+
+    let mut issues = 0;
+    for report in &reports {
+        println!("{}:", report.instance.display());
+        if report.issues.is_empty() {
+            println!("  No issues found");
+        }
+        for issue in &report.issues {
+            match &issue.path {
+                Some(path) => println!("  {} ({})", issue.message, path.display()),
+                None => println!("  {}", issue.message),
+            }
+        }
+        issues += report.issues.len();
+    }
+
+    if issues > 0 {
+        if !args.repair {
+            println!("Run with --repair to fix these issues");
+        }
+        return 1;
+    }
+    0
+}
+
+/// Asks about every issue of every instance and repairs the ones that were confirmed
+fn repair_interactively(args: &HeadlessArgs) -> Result<Vec<InstanceReport>, String> {
+    let portable = args.portable.as_deref();
+    let launcher_path = args.launcher_path.as_deref();
+    let mut stdin = std::io::stdin().lock();
+
+    let mut reports = Vec::new();
+    for report in installer::verify(portable, launcher_path)? {
+        if report.issues.is_empty() {
+            reports.push(report);
+            continue;
+        }
+
+        println!("{}:", report.instance.display());
+        let kinds = confirm_fixes(&report, &mut stdin);
+        if kinds.is_empty() {
+            reports.push(report);
+            continue;
+        }
+        reports.extend(tauri::async_runtime::block_on(installer::repair(
+            portable,
+            launcher_path,
+            Some(&report.instance),
+            Some(&kinds),
+        ))?);
+    }
+    Ok(reports)
+}
+
+/// Asks whether to fix each issue, returns the kinds that should be fixed
+fn confirm_fixes(report: &InstanceReport, input: &mut impl BufRead) -> Vec<IssueKind> {
+    let mut kinds = Vec::new();
+    for issue in &report.issues {
+        match &issue.path {
+            Some(path) => print!("  {} ({})", issue.message, path.display()),
+            None => print!("  {}", issue.message),
+        }
+        // Issues of one kind are repaired together
+        if kinds.contains(&issue.kind) {
+            println!(", fixed with the above");
+            continue;
+        }
+        print!(". Fix it? [y/N] ");
+        let _ = std::io::stdout().flush();
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer).unwrap_or(0) == 0 {
+            println!();
+            break;
+        }
+        if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            kinds.push(issue.kind);
+        }
+    }
+    kinds
+}
+
+fn sync(args: &HeadlessArgs) -> i32 {
+    let res = tauri::async_runtime::block_on(installer::sync(
+        args.portable.as_deref(),
+        args.launcher_path.as_deref(),
+        &args.download,
+        &StdoutProgress,
+    ));
+
+    match res {
+        Ok(reports) => {
+            for (instance, report) in &reports {
+                println!(
+                    "{}: {} downloaded, {} removed, {} unchanged",
+                    instance.display(),
+                    report.downloaded.len(),
+                    report.removed.len(),
+                    report.unchanged
+                );
+                if !report.skipped.is_empty() {
+                    println!(
+                        "  {} files need the packwiz bootstrap, they're downloaded on the next launch",
+                        report.skipped.len()
+                    );
+                }
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Sync failed: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::Issue;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|a| a.to_string()).collect())
+    }
+
+    fn headless(args: &[&str]) -> HeadlessArgs {
+        match parse(args) {
+            Ok(Command::Headless(args)) => args,
+            other => panic!("expected a headless run for {:?}, got {:?}", args, other),
+        }
+    }
+
+    #[test]
+    fn no_arguments_start_the_window() {
+        assert!(matches!(parse(&[]), Ok(Command::Window { ignored }) if ignored.is_empty()));
+    }
+
+    #[test]
+    fn unknown_arguments_still_start_the_window() {
+        for args in [
+            &["-psn_0_12345"][..],
+            &["C:\\Users\\Player\\Downloads"],
+            &["a", "b"],
+        ] {
+            match parse(args) {
+                Ok(Command::Window { ignored }) => assert_eq!(ignored, args),
+                other => panic!("expected the window for {:?}, got {:?}", args, other),
+            }
+        }
+    }
+
+    #[test]
+    fn help_wins_over_everything_else() {
+        for args in [
+            &["--help"][..],
+            &["-h"],
+            &["--headless", "--help"],
+            &["--portable", "-h"],
+            &["--bogus", "--help"],
+        ] {
+            assert!(matches!(parse(args), Ok(Command::Help)), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn parses_install_options() {
+        let args = headless(&[
+            "--headless",
+            "--portable",
+            "D:\\Prism",
+            "--no-launch",
+            "--new-instance",
+            "--retries",
+            "2",
+            "--stall-timeout",
+            "10",
+        ]);
+        assert_eq!(args.portable, Some(PathBuf::from("D:\\Prism")));
+        assert!(args.no_launch && args.new_instance);
+        assert_eq!(args.download.retries, 2);
+        assert_eq!(args.download.stall_timeout, Duration::from_secs(10));
+        assert!(!args.uninstall && !args.verify && !args.repair && !args.sync);
+    }
+
+    #[test]
+    fn defaults_match_download_policy() {
+        let args = headless(&["--headless"]);
+        assert_eq!(args.download.retries, DownloadPolicy::default().retries);
+        assert_eq!(args.portable, None);
+        assert_eq!(args.launcher_path, None);
+    }
+
+    #[test]
+    fn mode_flags_run_headless() {
+        assert!(headless(&["--uninstall", "--backup-saves", "saves"]).uninstall);
+        assert!(headless(&["--verify", "--launcher-path", "prism"]).verify);
+        assert!(headless(&["--repair"]).repair);
+        assert!(headless(&["--sync", "--portable", "dir"]).sync);
+    }
+
+    #[test]
+    fn parses_repair_kinds() {
+        let args = headless(&["--repair", "--portable", "dir"]);
+        assert!(args.repair);
+        assert_eq!(args.repair_kinds, None);
+
+        let args = headless(&[
+            "--repair",
+            "missing_icon, broken_shortcut",
+            "--portable",
+            "dir",
+        ]);
+        assert_eq!(
+            args.repair_kinds,
+            Some(vec![IssueKind::MissingIcon, IssueKind::BrokenShortcut])
+        );
+        assert_eq!(args.portable, Some(PathBuf::from("dir")));
+
+        let args = headless(&["--repair", "all"]);
+        assert_eq!(args.repair_kinds, Some(IssueKind::ALL.to_vec()));
+
+        assert!(parse(&["--repair", "missing_icon,everything"]).is_err());
+    }
+
+    #[test]
+    fn asks_about_every_issue() {
+        let issue = |kind, message: &str| Issue {
+            kind,
+            message: message.to_string(),
+            path: None,
+        };
+        let report = InstanceReport {
+            instance: PathBuf::from("Pack"),
+            issues: vec![
+                issue(IssueKind::MissingIcon, "Icon is missing"),
+                issue(IssueKind::MissingShortcut, "Shortcut is missing"),
+                issue(IssueKind::MissingShortcut, "Shortcut is missing"),
+                issue(IssueKind::OverrideCommands, "Custom commands are disabled"),
+            ],
+        };
+
+        // The second missing shortcut is fixed with the first one without asking
+        let kinds = confirm_fixes(&report, &mut "n\nY\nyes\n".as_bytes());
+        assert_eq!(
+            kinds,
+            [IssueKind::MissingShortcut, IssueKind::OverrideCommands]
+        );
+
+        // Stops asking once the input ends
+        let kinds = confirm_fixes(&report, &mut "y\n".as_bytes());
+        assert_eq!(kinds, [IssueKind::MissingIcon]);
+    }
+
+    #[test]
+    fn options_without_a_mode_are_rejected() {
+        for args in [
+            &["--portable", "dir"][..],
+            &["--launcher-path", "prism"],
+            &["--backup-saves", "saves"],
+            &["--no-launch"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        for args in [
+            &["--headless", "--bogus"][..],
+            &["--bogus", "--sync"],
+            &["--no-launch", "-psn_0_12345"],
+            &["--headless", "--portable"],
+            &["--headless", "--retries", "many"],
+            &["--headless", "--stall-timeout", "0"],
+            &[
+                "--headless",
+                "--portable",
+                "dir",
+                "--launcher-path",
+                "prism",
+            ],
+            &["--verify", "--repair"],
+            &["--uninstall", "--sync"],
+            &["--headless", "--backup-saves", "saves"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+}
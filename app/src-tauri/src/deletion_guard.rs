@@ -32,7 +32,14 @@ impl Drop for TemporaryFileCleaner {
             return;
         }
 
-        if let Err(e) = std::fs::remove_file(&self.file) {
+        // Also used as a staging directory for extractions
+        let res = if self.file.is_dir() {
+            std::fs::remove_dir_all(&self.file)
+        } else {
+            std::fs::remove_file(&self.file)
+        };
+
+        if let Err(e) = res {
             log::debug!(
                 "Failed to delete temporary file {}: {}",
                 self.file.display(),
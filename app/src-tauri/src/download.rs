@@ -0,0 +1,213 @@
+use std::path::Path;
+use std::time::Duration;
+
+use async_stream::try_stream;
+use futures_core::Stream;
+use futures_util::StreamExt;
+use reqwest::header::{ETAG, LAST_MODIFIED, RANGE};
+use reqwest::StatusCode;
+use tokio::io::AsyncWriteExt;
+use tokio::time::timeout;
+
+/// How downloads deal with flaky connections
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadPolicy {
+    /// How often a failed download is resumed before giving up
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every retry after that
+    pub backoff: Duration,
+    /// How long a connection may go without receiving data before it's dropped and retried
+    pub stall_timeout: Duration,
+}
+
+impl Default for DownloadPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 5,
+            backoff: Duration::from_secs(1),
+            stall_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadProgress {
+    pub downloaded: u64,
+    /// Size reported by the server, if it sent a `Content-Length`
+    pub total: Option<u64>,
+    /// Current retry and the maximum number of retries, once the download had to be resumed
+    pub retry: Option<(u32, u32)>,
+}
+
+impl DownloadProgress {
+    pub fn fraction(&self) -> f32 {
+        match self.total {
+            Some(total) if total > 0 => (self.downloaded as f64 / total as f64) as f32,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Headers a server identifies the version of a file with, used to check it for changes later
+#[derive(Debug, Clone, Default)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum DownloadEvent {
+    Progress(DownloadProgress),
+    /// Validators of the response the file is downloaded from, sent before its first byte
+    Validators(Validators),
+}
+
+/// Whether a failed response is worth retrying, client errors like a 404 won't go away
+fn is_transient(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Downloads `url` to `dest`, reporting the number of bytes written after every chunk
+///
+/// Failed or stalled transfers are resumed with a range request where the previous attempt
+/// stopped, waiting longer before every retry.
+pub fn download(
+    url: &str,
+    dest: &Path,
+    policy: &DownloadPolicy,
+) -> impl Stream<Item = Result<DownloadProgress, anyhow::Error>> {
+    download_with_validators(url, dest, policy).filter_map(|event| async move {
+        match event {
+            Ok(DownloadEvent::Progress(progress)) => Some(Ok(progress)),
+            Ok(DownloadEvent::Validators(_)) => None,
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+/// Like [`download`], but also reports the validators of the downloaded file
+pub fn download_with_validators(
+    url: &str,
+    dest: &Path,
+    policy: &DownloadPolicy,
+) -> impl Stream<Item = Result<DownloadEvent, anyhow::Error>> {
+    let url = url.to_owned();
+    let dest = dest.to_owned();
+    let policy = *policy;
+    try_stream! {
+        log::info!("Downloading {} to {}", url, dest.display());
+        let client = reqwest::Client::builder()
+            .connect_timeout(policy.stall_timeout)
+            .build()?;
+
+        let mut file = tokio::fs::File::create(&dest).await?;
+        let mut downloaded = 0;
+        let mut total = None;
+        let mut retry = None;
+        let mut attempt = 0;
+        'retry: loop {
+            let error: anyhow::Error = 'attempt: {
+                let mut req = client.get(&url);
+                if downloaded > 0 {
+                    req = req.header(RANGE, format!("bytes={}-", downloaded));
+                }
+
+                let res = match timeout(policy.stall_timeout, req.send()).await {
+                    Ok(Ok(res)) => res,
+                    Ok(Err(e)) => break 'attempt e.into(),
+                    Err(_) => break 'attempt anyhow::anyhow!("Connecting timed out"),
+                };
+                let status = res.status();
+                let res = match res.error_for_status() {
+                    Ok(res) => res,
+                    Err(e) if is_transient(status) => break 'attempt e.into(),
+                    Err(e) => Err(e)?,
+                };
+
+                // Servers without range support send the whole file again
+                if downloaded > 0 && status != StatusCode::PARTIAL_CONTENT {
+                    log::warn!("Server doesn't support resuming, restarting download of {}", url);
+                    file = tokio::fs::File::create(&dest).await?;
+                    downloaded = 0;
+                }
+                total = res.content_length().map(|len| len + downloaded).or(total);
+                if downloaded == 0 {
+                    let header = |name| {
+                        res.headers()
+                            .get(name)
+                            .and_then(|v| v.to_str().ok())
+                            .map(str::to_string)
+                    };
+                    yield DownloadEvent::Validators(Validators {
+                        etag: header(ETAG),
+                        last_modified: header(LAST_MODIFIED),
+                    });
+                }
+                yield DownloadEvent::Progress(DownloadProgress { downloaded, total, retry });
+
+                let mut body = res.bytes_stream();
+                loop {
+                    match timeout(policy.stall_timeout, body.next()).await {
+                        Ok(Some(Ok(chunk))) => {
+                            file.write_all(&chunk).await?;
+                            downloaded += chunk.len() as u64;
+                            yield DownloadEvent::Progress(DownloadProgress { downloaded, total, retry });
+                        }
+                        Ok(Some(Err(e))) => break 'attempt e.into(),
+                        Ok(None) => break,
+                        Err(_) => break 'attempt anyhow::anyhow!(
+                            "No data received for {} seconds",
+                            policy.stall_timeout.as_secs()
+                        ),
+                    }
+                }
+
+                if total.is_some_and(|total| downloaded < total) {
+                    break 'attempt anyhow::anyhow!("Connection closed before the download finished");
+                }
+                file.flush().await?;
+                break 'retry;
+            };
+
+            attempt += 1;
+            if attempt > policy.retries {
+                Err(anyhow::anyhow!("{}, gave up after {} retries", error, policy.retries))?;
+            }
+
+            let wait = policy
+                .backoff
+                .saturating_mul(2u32.saturating_pow(attempt - 1));
+            log::warn!(
+                "Download of {} failed at {} bytes: {}, retrying in {:?} ({}/{})",
+                url,
+                downloaded,
+                error,
+                wait,
+                attempt,
+                policy.retries
+            );
+            file.flush().await?;
+            retry = Some((attempt, policy.retries));
+            yield DownloadEvent::Progress(DownloadProgress { downloaded, total, retry });
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Fails with a readable error if the sha256 of `path` isn't `expected`
+pub fn verify_sha256(path: &Path, expected: &str, what: &str) -> Result<(), anyhow::Error> {
+    let actual = crate::util::sha256_file(path)?;
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        anyhow::bail!(
+            "Checksum mismatch for {}: expected {}, got {}. The download is probably incomplete, please try again",
+            what,
+            expected.trim(),
+            actual
+        );
+    }
+
+    log::info!("Verified sha256 of {}", what);
+    Ok(())
+}
@@ -0,0 +1,327 @@
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+    /// `sha256:<hex>`, only set for assets uploaded after GitHub started hashing them
+    pub digest: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
+    pub assets: Vec<Asset>,
+}
+
+fn client() -> reqwest::Result<reqwest::Client> {
+    // The GitHub API rejects requests without a user agent
+    reqwest::Client::builder()
+        .user_agent(concat!("modpack-installer/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+/// Lists the most recent releases of `repo`, newest first
+pub async fn releases(repo: &str) -> Result<Vec<Release>, anyhow::Error> {
+    let url = format!(
+        "https://api.github.com/repos/{}/releases?per_page=100",
+        repo
+    );
+    let releases = client()?
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(releases)
+}
+
+/// Parses tags like `9.4`, `v8.0.1` or `9.4-beta`, filling in missing components with zeros
+pub fn tag_version(tag: &str) -> Option<Version> {
+    let tag = tag.strip_prefix('v').unwrap_or(tag);
+    // Only the numbers before a pre-release or build suffix get padded
+    let (core, suffix) = tag.split_at(tag.find(['-', '+']).unwrap_or(tag.len()));
+    let mut parts = core.split('.').collect::<Vec<_>>();
+    while parts.len() < 3 {
+        parts.push("0");
+    }
+
+    Version::parse(&format!("{}{}", parts.join("."), suffix)).ok()
+}
+
+fn newest_matching<'a>(releases: &'a [Release], req: &VersionReq) -> Option<&'a Release> {
+    releases
+        .iter()
+        .filter(|r| !r.draft && !r.prerelease)
+        .filter_map(|r| Some((tag_version(&r.tag_name)?, r)))
+        .filter(|(version, _)| req.matches(version))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+}
+
+/// Picks the newest release matching `version` and the asset `select` chooses from it
+///
+/// If `select` finds nothing usable in that release, `fallbacks` are tried in order.
+pub fn select_asset<'a>(
+    releases: &'a [Release],
+    version: &VersionReq,
+    fallbacks: &[VersionReq],
+    select: impl Fn(&'a [Asset]) -> Result<&'a Asset, anyhow::Error>,
+) -> Result<(&'a Release, &'a Asset), anyhow::Error> {
+    let mut tried = Vec::new();
+    for req in std::iter::once(version).chain(fallbacks) {
+        let Some(release) = newest_matching(releases, req) else {
+            tried.push(format!("{}: no release", req));
+            continue;
+        };
+
+        match select(&release.assets) {
+            Ok(asset) => return Ok((release, asset)),
+            Err(e) => {
+                log::warn!("Skipping release {}: {}", release.tag_name, e);
+                tried.push(format!("{}: {}", release.tag_name, e));
+            }
+        }
+    }
+
+    anyhow::bail!("No usable release found ({})", tried.join("; "))
+}
+
+/// Reads `<hash> <file name>` lines as written by `sha256sum`
+fn parse_checksums(content: &str, name: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let hash = parts.next()?;
+        let file = parts.next()?.trim_start_matches('*');
+        (file == name).then(|| hash.to_lowercase())
+    })
+}
+
+/// Looks up the sha256 the release publishes for `asset`, if any
+///
+/// Prefers the digest GitHub computed on upload, then `<asset>.sha256` files and finally
+/// checksum lists like `sha256sums.txt` attached to the release.
+pub async fn published_sha256(release: &Release, asset: &Asset) -> Option<String> {
+    if let Some(digest) = asset
+        .digest
+        .as_deref()
+        .and_then(|d| d.strip_prefix("sha256:"))
+    {
+        return Some(digest.to_lowercase());
+    }
+
+    let sidecar = format!("{}.sha256", asset.name);
+    let candidates = release.assets.iter().filter(|a| {
+        let name = a.name.to_lowercase();
+        a.name == sidecar || (name.contains("sha256") && name.ends_with(".txt"))
+    });
+
+    let client = client().ok()?;
+    for candidate in candidates {
+        let res = async {
+            let res = client
+                .get(&candidate.browser_download_url)
+                .send()
+                .await?
+                .error_for_status()?;
+            res.text().await
+        };
+        // A broken checksum file doesn't mean the other ones are broken too
+        let content = match res.await {
+            Ok(content) => content,
+            Err(e) => {
+                log::warn!("Failed to fetch checksums from {}: {}", candidate.name, e);
+                continue;
+            }
+        };
+
+        // Sidecar files often only contain the hash itself
+        if candidate.name == sidecar {
+            if let Some(hash) = content.split_whitespace().next() {
+                return Some(hash.to_lowercase());
+            }
+        }
+
+        if let Some(hash) = parse_checksums(&content, &asset.name) {
+            return Some(hash);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::test_util::Server;
+
+    fn release(tag: &str, assets: &[&str]) -> Release {
+        Release {
+            tag_name: tag.to_string(),
+            draft: false,
+            prerelease: false,
+            assets: assets
+                .iter()
+                .map(|name| Asset {
+                    name: name.to_string(),
+                    browser_download_url: format!("https://example.com/{}/{}", tag, name),
+                    digest: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn req(req: &str) -> VersionReq {
+        VersionReq::parse(req).unwrap()
+    }
+
+    /// Picks the first zip, like the launcher asset selection for a system with zips only
+    fn zip(assets: &[Asset]) -> Result<&Asset, anyhow::Error> {
+        assets
+            .iter()
+            .find(|a| a.name.ends_with(".zip"))
+            .ok_or_else(|| anyhow::anyhow!("no zip"))
+    }
+
+    #[test]
+    fn parses_tags() {
+        for (tag, expected) in [
+            ("9.4", Some("9.4.0")),
+            ("v9.4", Some("9.4.0")),
+            ("8.0.1", Some("8.0.1")),
+            ("v8.0.1", Some("8.0.1")),
+            ("10", Some("10.0.0")),
+            ("9.4-beta", Some("9.4.0-beta")),
+            ("v9.4.1-rc.2", Some("9.4.1-rc.2")),
+            ("9.4+build.5", Some("9.4.0+build.5")),
+            ("", None),
+            ("v", None),
+            ("latest", None),
+            ("9.x", None),
+            ("1.2.3.4", None),
+            ("vv9.4", None),
+            ("9..4", None),
+        ] {
+            let expected = expected.map(|v| Version::parse(v).unwrap());
+            assert_eq!(tag_version(tag), expected, "{:?}", tag);
+        }
+    }
+
+    #[test]
+    fn prereleases_only_match_requirements_asking_for_them() {
+        let version = tag_version("9.5-beta").unwrap();
+        assert!(!req("^9.4").matches(&version));
+        assert!(req(">=9.5.0-beta").matches(&version));
+    }
+
+    #[test]
+    fn selects_newest_matching_release() {
+        let mut draft = release("9.9", &["draft.zip"]);
+        draft.draft = true;
+        let mut beta = release("9.6-beta", &["beta.zip"]);
+        beta.prerelease = true;
+        let releases = [
+            draft,
+            beta,
+            release("9.4", &["9.4.zip"]),
+            release("9.5", &["9.5.zip"]),
+            release("8.4", &["8.4.zip"]),
+            release("nightly", &["nightly.zip"]),
+        ];
+
+        for (requirement, expected) in [
+            ("*", "9.5"),
+            ("^9", "9.5"),
+            ("~9.4", "9.4"),
+            ("=9.4.0", "9.4"),
+            ("<9", "8.4"),
+        ] {
+            let (release, asset) = select_asset(&releases, &req(requirement), &[], zip).unwrap();
+            assert_eq!(release.tag_name, expected, "{}", requirement);
+            assert_eq!(asset.name, format!("{}.zip", expected));
+        }
+    }
+
+    #[test]
+    fn falls_back_to_older_releases_in_order() {
+        let releases = [
+            release("9.5", &["setup.exe"]),
+            release("9.4", &["9.4.zip"]),
+            release("8.4", &["8.4.zip"]),
+        ];
+
+        for (fallbacks, expected) in [
+            // ^9.4 picks 9.5 again, which still has no zip
+            (vec![req("^9.4"), req("^8")], Ok("8.4")),
+            (vec![req("=9.4.0"), req("^8")], Ok("9.4")),
+            (vec![req("^7"), req("^8")], Ok("8.4")),
+            (vec![], Err("No usable release found (9.5: no zip)")),
+            (
+                vec![req("^7")],
+                Err("No usable release found (9.5: no zip; ^7: no release)"),
+            ),
+        ] {
+            let res = select_asset(&releases, &req("^9.5"), &fallbacks, zip)
+                .map(|(release, _)| release.tag_name.as_str())
+                .map_err(|e| e.to_string());
+            assert_eq!(res, expected.map_err(str::to_string), "{:?}", fallbacks);
+        }
+    }
+
+    #[test]
+    fn reads_checksum_lists() {
+        let content =
+            "ABC123  PrismLauncher-Linux.zip\ndef456 *PrismLauncher-Windows.zip\n\nbroken\n";
+        for (name, expected) in [
+            ("PrismLauncher-Linux.zip", Some("abc123")),
+            ("PrismLauncher-Windows.zip", Some("def456")),
+            ("PrismLauncher-macOS.zip", None),
+            ("broken", None),
+        ] {
+            assert_eq!(
+                parse_checksums(content, name).as_deref(),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn skips_checksum_files_whose_body_fails() {
+        // Promises more than it sends, so reading the body fails after a successful response
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let broken = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 1024]);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nabc");
+        });
+        let server = Server::new(&[("/sums.txt", b"abc123  PrismLauncher.zip\n")]);
+
+        let mut release = release("9.5", &["PrismLauncher.zip"]);
+        for (name, url) in [
+            ("PrismLauncher.zip.sha256", broken),
+            ("sha256sums.txt", server.url("/sums.txt")),
+        ] {
+            release.assets.push(Asset {
+                name: name.to_string(),
+                browser_download_url: url,
+                digest: None,
+            });
+        }
+
+        let hash = tauri::async_runtime::block_on(published_sha256(&release, &release.assets[0]));
+        assert_eq!(hash.as_deref(), Some("abc123"));
+    }
+}
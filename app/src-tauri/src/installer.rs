@@ -0,0 +1,524 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use futures_util::future::{abortable, AbortHandle};
+use futures_util::{pin_mut, StreamExt};
+use semver::VersionReq;
+
+use crate::assets::{self, AssetKind, Host};
+use crate::cache;
+use crate::deletion_guard::TemporaryFileCleaner;
+use crate::download::{verify_sha256, DownloadPolicy};
+use crate::github;
+use crate::instance::{self, PreviousInstall};
+use crate::modpack::{install_modpack, InstallOptions};
+use crate::offline::OfflinePayload;
+use crate::packwiz::{sync_mods, ModSync, SyncReport};
+use crate::platform::{self, UserPaths};
+use crate::preflight::preflight;
+use crate::progress::{Phase, ProgressEvent, ProgressSink, ProgressTracker, MODPACK_PHASES};
+use crate::uninstall::{self, UninstallReport};
+use crate::util::{self, ModpackConfig};
+use crate::verify::{self, InstanceReport, IssueKind};
+
+/// Tracks the install in progress so it can be cancelled from another command
+#[derive(Default)]
+pub struct RunningInstall {
+    abort: Mutex<Option<AbortHandle>>,
+}
+
+impl RunningInstall {
+    /// Runs `install` until it finishes or gets cancelled, only one install can run at a time
+    ///
+    /// Cancelling drops the install future, which stops its streams at the next await point, kills
+    /// a running PrismLauncher installer and rolls back the instance and icons created so far.
+    pub async fn run(
+        &self,
+        install: impl Future<Output = Result<(), String>>,
+    ) -> Result<(), String> {
+        let (install, handle) = abortable(install);
+        {
+            let mut abort = self.abort.lock().unwrap();
+            if abort.is_some() {
+                return Err("Another installation is already running".into());
+            }
+            *abort = Some(handle);
+        }
+
+        let res = install.await;
+        self.abort.lock().unwrap().take();
+
+        res.unwrap_or_else(|_| {
+            log::info!("Installation cancelled");
+            Err("Installation cancelled".into())
+        })
+    }
+
+    /// Cancels the running install, returns false if there was none
+    pub fn cancel(&self) -> bool {
+        match self.abort.lock().unwrap().take() {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+const PRISM_REPO: &str = "PrismLauncher/PrismLauncher";
+
+/// Downloads the best PrismLauncher build of `kind` for this machine to `dest`, returns its name
+///
+/// The release is picked by the version requirement in the modpack config, falling back to the
+/// configured fallback versions if it has no matching asset. The download is checked against the
+/// hash pinned in the config, or the one published with the release if the config doesn't pin it.
+/// A build for this machine bundled in the offline payload is used instead of any release.
+async fn download_launcher(
+    kind: AssetKind,
+    dest: &Path,
+    policy: &DownloadPolicy,
+    cache_dir: Option<&Path>,
+    tracker: &mut ProgressTracker<'_>,
+) -> Result<String, String> {
+    let config =
+        util::read_metadata().map_err(|e| format!("Failed to get modpack config: {}", e))?;
+    if let Some(payload) = OfflinePayload::locate() {
+        let bundled = payload.launcher_assets();
+        match assets::select(&bundled, Host::current(), kind) {
+            Ok(asset) => {
+                payload
+                    .extract_launcher(asset, dest)
+                    .map_err(|e| format!("Failed to extract bundled PrismLauncher: {}", e))?;
+                if let Some(pinned) = config.launcher_sha256.get(&asset.name) {
+                    verify_sha256(dest, pinned, &asset.name).map_err(|e| e.to_string())?;
+                }
+
+                tracker.update(ProgressEvent::new(
+                    Phase::DownloadLauncher,
+                    1.0,
+                    format!("Using bundled {}", asset.name),
+                ));
+                return Ok(asset.name.clone());
+            }
+            Err(e) if !bundled.is_empty() => log::warn!("Not using bundled PrismLauncher: {}", e),
+            Err(_) => {}
+        }
+    }
+
+    let parse = |req: &str| {
+        VersionReq::parse(req)
+            .map_err(|e| format!("Invalid PrismLauncher version '{}': {}", req, e))
+    };
+    let version = match &config.launcher_version {
+        Some(req) => parse(req)?,
+        None => VersionReq::STAR,
+    };
+    let fallbacks = config
+        .launcher_fallback_versions
+        .iter()
+        .map(|req| parse(req))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let releases = github::releases(PRISM_REPO)
+        .await
+        .map_err(|e| format!("Failed to fetch PrismLauncher releases: {}", e))?;
+    let (release, asset) = github::select_asset(&releases, &version, &fallbacks, |assets| {
+        assets::select(assets, Host::current(), kind)
+    })
+    .map_err(|e| format!("Failed to find PrismLauncher download: {}", e))?;
+    log::info!("Using PrismLauncher {} ({})", release.tag_name, asset.name);
+
+    let pinned = config.launcher_sha256.get(&asset.name).map(String::as_str);
+    fetch_release_asset(release, asset, pinned, dest, policy, cache_dir, tracker).await?;
+    Ok(asset.name.clone())
+}
+
+/// Downloads `asset` of `release` to `dest`, checked against `pinned` or the published hash
+///
+/// The hash is known before the download starts, so a file that doesn't match it is never cached.
+async fn fetch_release_asset(
+    release: &github::Release,
+    asset: &github::Asset,
+    pinned: Option<&str>,
+    dest: &Path,
+    policy: &DownloadPolicy,
+    cache_dir: Option<&Path>,
+    tracker: &mut ProgressTracker<'_>,
+) -> Result<(), String> {
+    let published = match pinned {
+        Some(_) => None,
+        None => github::published_sha256(release, asset).await,
+    };
+    let expected = pinned.or(published.as_deref());
+    if expected.is_none() {
+        log::warn!(
+            "No checksum published for {}, skipping verification",
+            asset.name
+        );
+    }
+
+    let msg = format!("Downloading {}", asset.name);
+    let download = cache::fetch(
+        cache_dir,
+        &asset.browser_download_url,
+        dest,
+        policy,
+        expected,
+    );
+    pin_mut!(download);
+    while let Some(res) = download.next().await {
+        let bytes = res.map_err(|e| format!("Error downloading PrismLauncher: {}", e))?;
+        tracker.update(ProgressEvent::download(
+            Phase::DownloadLauncher,
+            bytes,
+            msg.clone(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks every download of the modpack before the install writes anything
+async fn run_preflight(
+    options: &InstallOptions,
+    tracker: &mut ProgressTracker<'_>,
+) -> Result<ModpackConfig, String> {
+    tracker.update(ProgressEvent::new(
+        Phase::Preflight,
+        0.0,
+        "Checking modpack downloads",
+    ));
+
+    let config =
+        util::read_metadata().map_err(|e| format!("Failed to get modpack config: {}", e))?;
+    preflight(&config, &options.download, options.paths.cache.as_deref())
+        .await
+        .map_err(|e| format!("Modpack downloads aren't available:\n{}", e))?;
+
+    tracker.update(ProgressEvent::new(
+        Phase::Preflight,
+        1.0,
+        "All modpack downloads are available",
+    ));
+    Ok(config)
+}
+
+/// Downloads a portable PrismLauncher into `path` and installs the modpack into it
+pub async fn install_portable(
+    path: &Path,
+    options: InstallOptions,
+    progress: &dyn ProgressSink,
+) -> Result<(), String> {
+    // Validate path
+    if !path.exists() {
+        return Err("The specified path doesn't exist".into());
+    }
+
+    if !path.is_dir() {
+        return Err("The specified path is not a directory".into());
+    }
+
+    let phases = [
+        (Phase::Preflight, 2.0),
+        (Phase::DownloadLauncher, 25.0),
+        (Phase::ExtractLauncher, 10.0),
+    ];
+    let mut tracker = ProgressTracker::new(progress, &[&phases[..], &MODPACK_PHASES[..]].concat());
+    let config = run_preflight(&options, &mut tracker).await?;
+
+    // Emit progress update
+    tracker.update(ProgressEvent::new(
+        Phase::DownloadLauncher,
+        0.0,
+        "Starting portable installation...",
+    ));
+
+    // Installing PrismLauncher
+    let tmp_file = TemporaryFileCleaner::new();
+    download_launcher(
+        AssetKind::Portable,
+        tmp_file.file_path(),
+        &options.download,
+        options.paths.cache.as_deref(),
+        &mut tracker,
+    )
+    .await?;
+
+    tracker.update(ProgressEvent::new(
+        Phase::ExtractLauncher,
+        0.0,
+        "Extracting PrismLauncher",
+    ));
+
+    log::info!("Extracting PrismLauncher to: {}", path.display());
+
+    // Extracting PrismLauncher
+    let extract = platform::extract_portable(tmp_file.file_path(), path);
+    pin_mut!(extract);
+
+    while let Some(res) = extract.next().await {
+        let (percentage, msg) =
+            res.map_err(|e| format!("Error extracting PrismLauncher: {}", e))?;
+
+        tracker.update(ProgressEvent::new(Phase::ExtractLauncher, percentage, msg));
+    }
+
+    let install = install_modpack(path, &platform::portable_exec(path), config, options);
+    pin_mut!(install);
+
+    while let Some(res) = install.next().await {
+        let event = res.map_err(|e| format!("Error installing modpack: {}", e))?;
+        tracker.update(event);
+    }
+    Ok(())
+}
+
+/// Installs the modpack into an existing PrismLauncher, installing the launcher first if needed
+pub async fn use_or_install_launcher(
+    custom_path: Option<PathBuf>,
+    options: InstallOptions,
+    progress: &dyn ProgressSink,
+) -> Result<(), String> {
+    let path = custom_path.or(platform::get_prism_launcher_exec().ok().flatten());
+
+    log::info!("PrismLauncher path: {:?}", path);
+    let mut phases = vec![(Phase::Preflight, 2.0)];
+    if path.is_none() {
+        phases.push((Phase::DownloadLauncher, 25.0));
+        phases.push((Phase::InstallLauncher, 10.0));
+    }
+    phases.extend(MODPACK_PHASES);
+    let mut tracker = ProgressTracker::new(progress, &phases);
+    let config = run_preflight(&options, &mut tracker).await?;
+
+    let path = if path.is_some() {
+        path
+    } else {
+        let tmp_file = TemporaryFileCleaner::new();
+        let asset_name = download_launcher(
+            AssetKind::Installer,
+            tmp_file.file_path(),
+            &options.download,
+            options.paths.cache.as_deref(),
+            &mut tracker,
+        )
+        .await?;
+
+        tracker.update(ProgressEvent::new(
+            Phase::InstallLauncher,
+            0.0,
+            "Installing PrismLauncher",
+        ));
+
+        let installed =
+            platform::install_launcher(&options.paths, tmp_file.file_path(), &asset_name).await?;
+
+        tracker.update(ProgressEvent::new(
+            Phase::InstallLauncher,
+            1.0,
+            "Installing modpack...",
+        ));
+        installed
+    };
+
+    if path.is_none() {
+        return Err("PrismLauncher installation canceled.".into());
+    }
+
+    let path = path.unwrap();
+    let path = Path::new(&path);
+    let data = platform::get_prism_launcher_data_for(path)
+        .ok_or("Failed to determine PrismLauncher data directory")?;
+
+    log::info!("PrismLauncher data directory: {:?}", data);
+    let install = install_modpack(&data, path, config, options);
+    pin_mut!(install);
+
+    while let Some(res) = install.next().await {
+        let event = res.map_err(|e| format!("Error installing modpack: {}", e))?;
+        tracker.update(event);
+    }
+    Ok(())
+}
+
+/// Finds the PrismLauncher data directory and executable to work on
+///
+/// `portable` is a portable install or data directory, `launcher_path` a PrismLauncher executable.
+/// Without either, the installed launcher is detected.
+fn locate_launcher(
+    portable: Option<&Path>,
+    launcher_path: Option<&Path>,
+) -> Result<(PathBuf, Option<PathBuf>), String> {
+    let detect_exec = || platform::get_prism_launcher_exec().ok().flatten();
+    match (portable, launcher_path) {
+        (Some(dir), _) => {
+            let exec = Some(platform::portable_exec(dir))
+                .filter(|e| e.exists())
+                .or_else(detect_exec);
+            Ok((dir.to_path_buf(), exec))
+        }
+        (None, Some(exe)) => {
+            let data = platform::get_prism_launcher_data_for(exe)
+                .ok_or("Failed to determine PrismLauncher data directory")?;
+            Ok((data, Some(exe.to_path_buf())))
+        }
+        (None, None) => {
+            let data = platform::get_prism_launcher_data()?
+                .ok_or("Failed to find the PrismLauncher data directory")?;
+            Ok((data, detect_exec()))
+        }
+    }
+}
+
+/// Removes the modpack from the portable install in `portable` or from the PrismLauncher at
+/// `launcher_path`, auto-detecting the launcher if neither is given
+pub fn uninstall(
+    portable: Option<&Path>,
+    launcher_path: Option<&Path>,
+    backup_dir: Option<&Path>,
+) -> Result<UninstallReport, String> {
+    let (data, _) = locate_launcher(portable, launcher_path)?;
+    let config = util::read_metadata().map_err(|e| e.to_string())?;
+    uninstall::uninstall(&UserPaths::current(), &data, &config, backup_dir)
+        .map_err(|e| format!("{:#}", e))
+}
+
+fn find_installs(data: &Path, config: &ModpackConfig) -> Result<Vec<PreviousInstall>, String> {
+    let installs = instance::find_installs(&data.join("instances"), config);
+    if installs.is_empty() {
+        return Err(format!(
+            "{} is not installed in {}",
+            config.name,
+            data.display()
+        ));
+    }
+
+    Ok(installs)
+}
+
+/// Checks every installed instance of the modpack for problems
+pub fn verify(
+    portable: Option<&Path>,
+    launcher_path: Option<&Path>,
+) -> Result<Vec<InstanceReport>, String> {
+    let (data, _) = locate_launcher(portable, launcher_path)?;
+    let config = util::read_metadata().map_err(|e| e.to_string())?;
+
+    let reports = find_installs(&data, &config)?
+        .into_iter()
+        .map(|install| InstanceReport {
+            issues: verify::verify(&UserPaths::current(), &data, &install, &config),
+            instance: install.dir,
+        })
+        .collect();
+    Ok(reports)
+}
+
+/// Fixes the given kinds of issues, or all of them, in one or all installed instances
+pub async fn repair(
+    portable: Option<&Path>,
+    launcher_path: Option<&Path>,
+    instance: Option<&Path>,
+    kinds: Option<&[IssueKind]>,
+) -> Result<Vec<InstanceReport>, String> {
+    let (data, exec) = locate_launcher(portable, launcher_path)?;
+    let config = util::read_metadata().map_err(|e| e.to_string())?;
+
+    let mut reports = Vec::new();
+    for install in find_installs(&data, &config)? {
+        if instance.is_some_and(|i| i != install.dir) {
+            continue;
+        }
+
+        let issues = verify::repair(
+            &UserPaths::current(),
+            &data,
+            &install,
+            &config,
+            exec.as_deref(),
+            kinds.unwrap_or(&IssueKind::ALL),
+        )
+        .await
+        .map_err(|e| format!("Failed to repair {}: {}", install.dir.display(), e))?;
+
+        reports.push(InstanceReport {
+            instance: install.dir,
+            issues,
+        });
+    }
+
+    Ok(reports)
+}
+
+/// Syncs every installed instance of the modpack with its packwiz pack, without Java
+pub async fn sync(
+    portable: Option<&Path>,
+    launcher_path: Option<&Path>,
+    policy: &DownloadPolicy,
+    progress: &dyn ProgressSink,
+) -> Result<Vec<(PathBuf, SyncReport)>, String> {
+    let (data, _) = locate_launcher(portable, launcher_path)?;
+    let config = util::read_metadata().map_err(|e| e.to_string())?;
+    let mut tracker = ProgressTracker::new(progress, &[(Phase::SyncMods, 1.0)]);
+
+    let mut reports = Vec::new();
+    for install in find_installs(&data, &config)? {
+        let minecraft_dir = install.dir.join("minecraft");
+        let sync = sync_mods(&minecraft_dir, &config.packwiz_url, policy, None);
+        pin_mut!(sync);
+        while let Some(event) = sync.next().await {
+            match event.map_err(|e| format!("Failed to sync mods: {:#}", e))? {
+                ModSync::Progress(event) => tracker.update(event),
+                ModSync::Done(report) => reports.push((install.dir.clone(), report)),
+            }
+        }
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::MemoryProgress;
+    use crate::test_util::{self, Server};
+
+    #[test]
+    fn launchers_not_matching_the_published_hash_are_never_cached() {
+        let server = Server::new(&[("/PrismLauncher.zip", b"tampered")]);
+        let asset = github::Asset {
+            name: "PrismLauncher.zip".to_string(),
+            browser_download_url: server.url("/PrismLauncher.zip"),
+            digest: Some(format!("sha256:{}", "0".repeat(64))),
+        };
+        let release = github::Release {
+            tag_name: "9.5".to_string(),
+            draft: false,
+            prerelease: false,
+            assets: vec![asset.clone()],
+        };
+
+        let tmp = test_util::temp_dir();
+        let cache_dir = tmp.file_path().join("cache");
+        let dest = tmp.file_path().join("PrismLauncher.zip");
+        let sink = MemoryProgress::default();
+        let mut tracker = ProgressTracker::new(&sink, &[(Phase::DownloadLauncher, 1.0)]);
+        let res = tauri::async_runtime::block_on(fetch_release_asset(
+            &release,
+            &asset,
+            None,
+            &dest,
+            &DownloadPolicy::default(),
+            Some(&cache_dir),
+            &mut tracker,
+        ));
+
+        assert!(res.is_err());
+        assert!(!cache::is_cached(
+            Some(&cache_dir),
+            &asset.browser_download_url
+        ));
+    }
+}
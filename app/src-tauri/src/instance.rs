@@ -0,0 +1,422 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use configparser::ini::{Ini, WriteOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::journal::InstallJournal;
+use crate::pack::PackToml;
+use crate::util::{sha256_file, ModpackConfig};
+
+/// Written into every instance the installer creates, records what the install did
+pub const MANIFEST_FILE: &str = ".packwiz-installer.json";
+
+/// Player data that an update never overwrites, relative to the instance directory
+const PRESERVED: [&str; 4] = [
+    "minecraft/saves",
+    "minecraft/screenshots",
+    "minecraft/options.txt",
+    "minecraft/resourcepacks",
+];
+
+/// A file that was extracted from the base pack
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestFile {
+    /// Relative to the instance directory, always separated by `/`
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InstallManifest {
+    pub installer_version: String,
+    pub config: ModpackConfig,
+    /// Unix timestamps in seconds
+    pub installed_at: u64,
+    pub updated_at: u64,
+    pub icon_key: String,
+    pub shortcuts: Vec<PathBuf>,
+    pub files: Vec<ManifestFile>,
+}
+
+impl InstallManifest {
+    pub fn read(instance_dir: &Path) -> Option<Self> {
+        let content = std::fs::read(instance_dir.join(MANIFEST_FILE)).ok()?;
+        serde_json::from_slice(&content)
+            .map_err(|e| {
+                log::warn!(
+                    "Ignoring invalid install manifest in {}: {}",
+                    instance_dir.display(),
+                    e
+                )
+            })
+            .ok()
+    }
+
+    /// Names aren't unique, two packs only are the same if they sync from the same packwiz URL
+    fn matches(&self, config: &ModpackConfig) -> bool {
+        self.config.packwiz_url == config.packwiz_url
+    }
+}
+
+/// Seconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A previous install of the same modpack
+#[derive(Debug)]
+pub struct PreviousInstall {
+    pub dir: PathBuf,
+    /// Icon of the previous install, reused so updates don't leave old icons behind
+    pub icon_key: Option<String>,
+    /// `None` for instances created before the installer wrote manifests
+    pub manifest: Option<InstallManifest>,
+}
+
+/// Finds every instance of this modpack created by the installer, ones with a manifest first
+///
+/// Only instances syncing from the modpack's packwiz URL match, uninstalls rely on that to never
+/// delete another pack that happens to have the same name.
+pub fn find_installs(instances_dir: &Path, config: &ModpackConfig) -> Vec<PreviousInstall> {
+    let Ok(entries) = std::fs::read_dir(instances_dir) else {
+        return Vec::new();
+    };
+
+    let mut installs = Vec::new();
+    let mut legacy = Vec::new();
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        if !dir.is_dir() {
+            continue;
+        }
+
+        if let Some(manifest) = InstallManifest::read(&dir) {
+            if manifest.matches(config) {
+                installs.push(PreviousInstall {
+                    dir,
+                    icon_key: Some(manifest.icon_key.clone()),
+                    manifest: Some(manifest),
+                });
+            }
+            continue;
+        }
+
+        // Older installers didn't write a manifest, but they did set up the packwiz bootstrap
+        legacy.extend(read_legacy_install(&dir, &config.packwiz_url));
+    }
+
+    installs.extend(legacy);
+    installs
+}
+
+/// Looks for an instance of this modpack that was created by an earlier run of the installer
+pub fn find_previous_install(
+    instances_dir: &Path,
+    config: &ModpackConfig,
+) -> Option<PreviousInstall> {
+    find_installs(instances_dir, config).into_iter().next()
+}
+
+/// Reads an instance set up by an older installer, which must sync from `packwiz_url`
+fn read_legacy_install(instance_dir: &Path, packwiz_url: &str) -> Option<PreviousInstall> {
+    let mut cfg = Ini::new_cs();
+    cfg.load(instance_dir.join("instance.cfg")).ok()?;
+
+    let cmd = cfg.get("General", "PreLaunchCommand")?;
+    // The URL is the last argument, a URL merely ending in it belongs to another pack
+    let url_matches = cmd.split_whitespace().last() == Some(packwiz_url);
+    if !cmd.contains("packwiz_bootstrap.jar") || !url_matches {
+        return None;
+    }
+
+    Some(PreviousInstall {
+        dir: instance_dir.to_path_buf(),
+        icon_key: cfg.get("General", "iconKey"),
+        manifest: None,
+    })
+}
+
+fn is_preserved(relative: &Path) -> bool {
+    PRESERVED.iter().any(|p| relative.starts_with(p))
+}
+
+/// Copies freshly extracted base pack files over an existing instance, keeping player data
+///
+/// Files of the `previous` base pack that the new one dropped are removed. Returns the copied
+/// files relative to the instance directory.
+pub fn update_files(
+    staging: &Path,
+    instance_dir: &Path,
+    previous: &[ManifestFile],
+    journal: &mut InstallJournal,
+) -> std::io::Result<Vec<PathBuf>> {
+    let mut copied = Vec::new();
+    let mut pending = vec![staging.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let relative = path.strip_prefix(staging).unwrap();
+            if is_preserved(relative) {
+                log::info!("Keeping {} from the existing instance", relative.display());
+                continue;
+            }
+
+            let target = instance_dir.join(relative);
+            if entry.file_type()?.is_dir() {
+                journal.create_dir_all_sync(&target)?;
+                pending.push(path);
+            } else {
+                journal.copy(&path, &target)?;
+                copied.push(relative.to_path_buf());
+            }
+        }
+    }
+
+    let kept = copied
+        .iter()
+        .map(|f| manifest_path(f))
+        .collect::<HashSet<_>>();
+    for file in previous {
+        let relative = Path::new(&file.path);
+        // Manifests can be edited, so never follow one out of the instance
+        let inside = relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if kept.contains(&file.path) || is_preserved(relative) || !inside {
+            continue;
+        }
+
+        let target = instance_dir.join(relative);
+        if target.is_file() {
+            journal.remove(&target)?;
+            log::info!("Removed {}, the base pack dropped it", file.path);
+        }
+    }
+
+    Ok(copied)
+}
+
+/// Lists all files below `root`, relative to it
+pub fn list_files(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push(entry.path().strip_prefix(root).unwrap().to_path_buf());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// A relative path the way the manifest records it, separated by `/` on every platform
+fn manifest_path(file: &Path) -> String {
+    file.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Hashes `files`, which are relative to `root`, for the manifest
+pub fn hash_files(root: &Path, files: &[PathBuf]) -> std::io::Result<Vec<ManifestFile>> {
+    files
+        .iter()
+        .map(|file| {
+            Ok(ManifestFile {
+                path: manifest_path(file),
+                sha256: sha256_file(&root.join(file))?,
+            })
+        })
+        .collect()
+}
+
+/// Writes the `instance.cfg` and `mmc-pack.json` of a new instance for `pack` into `dir`
+///
+/// Used instead of extracting a base pack when the modpack doesn't have one.
+pub fn generate(dir: &Path, name: &str, pack: &PackToml) -> Result<(), anyhow::Error> {
+    std::fs::create_dir_all(dir)?;
+
+    let mut cfg = Ini::new_cs();
+    cfg.set("General", "ConfigVersion", Some("1.2".to_string()));
+    cfg.set("General", "InstanceType", Some("OneSix".to_string()));
+    cfg.set("General", "name", Some(name.to_string()));
+    let write_options = WriteOptions::new_with_params(true, 2, 1);
+    cfg.pretty_write(dir.join("instance.cfg"), &write_options)?;
+
+    std::fs::write(
+        dir.join("mmc-pack.json"),
+        serde_json::to_vec_pretty(&pack.mmc_pack())?,
+    )?;
+
+    log::info!(
+        "Generated instance for Minecraft {} in {}",
+        pack.versions.minecraft,
+        dir.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, install, legacy_install};
+
+    const URL: &str = "https://example.com/pack/pack.toml";
+    const OTHER_URL: &str = "https://example.com/other/pack.toml";
+
+    fn names(installs: &[PreviousInstall]) -> Vec<String> {
+        let mut names = installs
+            .iter()
+            .map(|i| i.dir.file_name().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn manifests_match_by_packwiz_url_only() {
+        let tmp = test_util::temp_dir();
+        let instances = tmp.file_path();
+        install(instances, "Pack", "Pack", URL);
+        install(instances, "Renamed", "Renamed", URL);
+        install(instances, "Pack (0)", "Pack", OTHER_URL);
+
+        let config = test_util::config("Pack", URL);
+        let installs = find_installs(instances, &config);
+        assert_eq!(names(&installs), ["Pack", "Renamed"]);
+        assert!(installs.iter().all(|i| i.manifest.is_some()));
+    }
+
+    #[test]
+    fn legacy_installs_match_by_prelaunch_command() {
+        let tmp = test_util::temp_dir();
+        let instances = tmp.file_path();
+        legacy_install(instances, "Renamed", URL);
+        legacy_install(instances, "Pack", OTHER_URL);
+        let proxied = format!("https://proxy.example.com/?url={}", URL);
+        legacy_install(instances, "Proxied", &proxied);
+        install(instances, "Manifest", "Pack", URL);
+
+        let installs = find_installs(instances, &test_util::config("Pack", URL));
+        assert_eq!(names(&installs), ["Manifest", "Renamed"]);
+        // Instances with a manifest come first
+        assert!(installs[0].manifest.is_some());
+        assert_eq!(installs[1].icon_key.as_deref(), Some("legacy"));
+    }
+
+    #[test]
+    fn updates_never_take_over_legacy_installs_of_other_packs() {
+        let tmp = test_util::temp_dir();
+        let instances = tmp.file_path();
+        legacy_install(instances, "Pack", OTHER_URL);
+
+        let config = test_util::config("Pack", URL);
+        assert!(find_installs(instances, &config).is_empty());
+        assert!(find_previous_install(instances, &config).is_none());
+    }
+
+    #[test]
+    fn updates_never_take_over_other_packs_by_name() {
+        let tmp = test_util::temp_dir();
+        let instances = tmp.file_path();
+        install(instances, "Pack", "Pack", OTHER_URL);
+
+        let config = test_util::config("Pack", URL);
+        assert!(find_previous_install(instances, &config).is_none());
+    }
+
+    #[test]
+    fn updates_remove_files_the_base_pack_dropped() {
+        let tmp = test_util::temp_dir();
+        let instances = tmp.file_path();
+        let instance = instances.join("Pack");
+        let staging = instances.join("staging");
+        let write = |path: PathBuf, content: &str| {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        for file in [
+            "instance.cfg",
+            "minecraft/config/kept.toml",
+            "minecraft/config/dropped.toml",
+            "minecraft/options.txt",
+            "minecraft/config/players.toml",
+        ] {
+            write(instance.join(file), "old");
+        }
+        write(staging.join("instance.cfg"), "new");
+        write(staging.join("minecraft/config/kept.toml"), "new");
+        std::fs::write(instances.join("outside.txt"), "outside").unwrap();
+
+        let previous = [
+            "instance.cfg",
+            "minecraft/config/kept.toml",
+            "minecraft/config/dropped.toml",
+            "minecraft/options.txt",
+            "../outside.txt",
+        ]
+        .map(|path| ManifestFile {
+            path: path.to_string(),
+            sha256: String::new(),
+        });
+
+        let mut journal = InstallJournal::new(&instance);
+        update_files(&staging, &instance, &previous, &mut journal).unwrap();
+        let read = |path: &str| std::fs::read_to_string(instance.join(path)).ok();
+        assert_eq!(read("minecraft/config/kept.toml").as_deref(), Some("new"));
+        assert_eq!(read("minecraft/config/dropped.toml"), None);
+        // Player data and files the base pack never had stay
+        assert_eq!(read("minecraft/options.txt").as_deref(), Some("old"));
+        assert_eq!(
+            read("minecraft/config/players.toml").as_deref(),
+            Some("old")
+        );
+        assert!(instances.join("outside.txt").exists());
+
+        drop(journal);
+        assert_eq!(
+            read("minecraft/config/dropped.toml").as_deref(),
+            Some("old")
+        );
+        assert_eq!(read("instance.cfg").as_deref(), Some("old"));
+    }
+
+    #[test]
+    fn generates_instances_from_pack_toml() {
+        let tmp = test_util::temp_dir();
+        let dir = tmp.file_path().join("Pack");
+        let pack = crate::pack::parse(
+            "name = \"Pack\"\n\n[index]\nfile = \"index.toml\"\nhash-format = \"sha256\"\n\
+             hash = \"ab\"\n\n[versions]\nminecraft = \"1.20.1\"\nforge = \"47.3.0\"\n",
+        )
+        .unwrap();
+        generate(&dir, "My Pack", &pack).unwrap();
+
+        let mut cfg = Ini::new_cs();
+        cfg.load(dir.join("instance.cfg")).unwrap();
+        assert_eq!(cfg.get("General", "name").as_deref(), Some("My Pack"));
+        assert_eq!(
+            cfg.get("General", "InstanceType").as_deref(),
+            Some("OneSix")
+        );
+
+        let mmc_pack: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.join("mmc-pack.json")).unwrap()).unwrap();
+        assert_eq!(mmc_pack, pack.mmc_pack());
+        assert_eq!(mmc_pack["components"][1]["uid"], "net.minecraftforge");
+    }
+}
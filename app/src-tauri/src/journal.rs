@@ -0,0 +1,239 @@
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+#[derive(Debug)]
+enum Entry {
+    CreatedDir(PathBuf),
+    CreatedFile(PathBuf),
+    ModifiedFile { path: PathBuf, backup: PathBuf },
+}
+
+/// Records every change an install makes to the Prism data directory
+///
+/// Unless the journal gets committed, dropping it undoes all recorded changes in reverse order.
+/// This covers both failed installs and installs whose future got dropped on cancellation.
+#[derive(Debug)]
+pub struct InstallJournal {
+    entries: Vec<Entry>,
+    /// Holds the original contents of modified files until the journal is done
+    backup_dir: PathBuf,
+    committed: bool,
+}
+
+impl InstallJournal {
+    /// Creates a journal that keeps its backups in a hidden directory next to `instance_dir`
+    pub fn new(instance_dir: &Path) -> Self {
+        let name = format!(".modpack-installer-backup-{}", Uuid::new_v4());
+        Self {
+            entries: Vec::new(),
+            backup_dir: instance_dir.with_file_name(name),
+            committed: false,
+        }
+    }
+
+    /// Records that `dir` is about to be created by someone else, e.g. while extracting into it
+    pub fn track_dir(&mut self, dir: &Path) {
+        if !dir.exists() {
+            self.entries
+                .push(Entry::CreatedDir(Self::first_missing(dir)));
+        }
+    }
+
+    /// Records a file that was created by someone else
+    pub fn track_file(&mut self, path: &Path) {
+        self.entries.push(Entry::CreatedFile(path.to_path_buf()));
+    }
+
+    /// Backs up `path` before it gets modified, new files are recorded as created instead
+    pub fn backup(&mut self, path: &Path) -> std::io::Result<()> {
+        if !path.exists() {
+            self.entries.push(Entry::CreatedFile(path.to_path_buf()));
+            return Ok(());
+        }
+
+        // Numbered by entry, the same file may be backed up more than once
+        std::fs::create_dir_all(&self.backup_dir)?;
+        let backup = self.backup_dir.join(self.entries.len().to_string());
+        std::fs::copy(path, &backup)?;
+        self.entries.push(Entry::ModifiedFile {
+            path: path.to_path_buf(),
+            backup,
+        });
+        Ok(())
+    }
+
+    /// Creates `dir` and its missing parents
+    pub async fn create_dir_all(&mut self, dir: &Path) -> std::io::Result<()> {
+        self.track_dir(dir);
+        tokio::fs::create_dir_all(dir).await
+    }
+
+    /// Blocking version of [`Self::create_dir_all`]
+    pub fn create_dir_all_sync(&mut self, dir: &Path) -> std::io::Result<()> {
+        self.track_dir(dir);
+        std::fs::create_dir_all(dir)
+    }
+
+    /// Copies `from` over `to`, keeping the previous contents of `to` around for rollback
+    pub fn copy(&mut self, from: &Path, to: &Path) -> std::io::Result<()> {
+        self.backup(to)?;
+        std::fs::copy(from, to).map(|_| ())
+    }
+
+    /// Writes `contents` to `path`, keeping the previous contents around for rollback
+    pub async fn write(&mut self, path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+        self.backup(path)?;
+        tokio::fs::write(path, contents).await
+    }
+
+    /// Removes the file at `path`, keeping its contents around for rollback
+    pub fn remove(&mut self, path: &Path) -> std::io::Result<()> {
+        self.backup(path)?;
+        std::fs::remove_file(path)
+    }
+
+    /// Keeps all changes, called once the install succeeded
+    pub fn commit(mut self) {
+        self.committed = true;
+        self.remove_backups();
+    }
+
+    fn first_missing(dir: &Path) -> PathBuf {
+        let mut missing = dir;
+        while let Some(parent) = missing.parent() {
+            if parent.as_os_str().is_empty() || parent.exists() {
+                break;
+            }
+            missing = parent;
+        }
+        missing.to_path_buf()
+    }
+
+    fn rollback(&mut self) {
+        while let Some(entry) = self.entries.pop() {
+            let (path, res) = match &entry {
+                Entry::CreatedDir(path) => (path, std::fs::remove_dir_all(path)),
+                Entry::CreatedFile(path) => (path, std::fs::remove_file(path)),
+                Entry::ModifiedFile { path, backup } => {
+                    (path, std::fs::copy(backup, path).map(|_| ()))
+                }
+            };
+
+            match res {
+                Ok(()) => log::info!("Rolled back {}", path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => log::warn!("Failed to roll back {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    fn remove_backups(&self) {
+        match std::fs::remove_dir_all(&self.backup_dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!(
+                "Failed to remove backups at {}: {}",
+                self.backup_dir.display(),
+                e
+            ),
+        }
+    }
+}
+
+impl Drop for InstallJournal {
+    fn drop(&mut self) {
+        if !self.committed && !self.entries.is_empty() {
+            log::info!(
+                "Install didn't finish, rolling back {} changes",
+                self.entries.len()
+            );
+            self.rollback();
+            self.remove_backups();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deletion_guard::TemporaryFileCleaner;
+    use crate::test_util;
+
+    fn temp_instance() -> (TemporaryFileCleaner, PathBuf) {
+        let root = test_util::temp_dir();
+        let instance = root.file_path().join("instances/Pack");
+        std::fs::create_dir_all(&instance).unwrap();
+        std::fs::write(instance.join("instance.cfg"), "original").unwrap();
+        (root, instance)
+    }
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn drop_without_commit_restores_everything() {
+        let (_root, instance) = temp_instance();
+        let cfg = instance.join("instance.cfg");
+        {
+            let mut journal = InstallJournal::new(&instance);
+            journal.backup(&cfg).unwrap();
+            std::fs::write(&cfg, "changed").unwrap();
+            journal.backup(&cfg).unwrap();
+            std::fs::write(&cfg, "changed again").unwrap();
+
+            journal
+                .create_dir_all_sync(&instance.join("minecraft/mods"))
+                .unwrap();
+            journal
+                .copy(&cfg, &instance.join("minecraft/options.txt"))
+                .unwrap();
+
+            // Backups live on disk next to the instance, not in memory
+            let backup_dir = journal.backup_dir.clone();
+            assert_eq!(backup_dir.parent(), instance.parent());
+            assert_eq!(std::fs::read(backup_dir.join("0")).unwrap(), b"original");
+        }
+
+        assert_eq!(std::fs::read_to_string(&cfg).unwrap(), "original");
+        assert!(!instance.join("minecraft").exists());
+        assert_eq!(entries(instance.parent().unwrap()), ["Pack"]);
+        assert_eq!(entries(&instance), ["instance.cfg"]);
+    }
+
+    #[test]
+    fn rollback_removes_created_instances() {
+        let (_root, instance) = temp_instance();
+        let instance = instance.with_file_name("New Pack");
+        {
+            let mut journal = InstallJournal::new(&instance);
+            journal.track_dir(&instance);
+            std::fs::create_dir_all(instance.join("minecraft")).unwrap();
+            let cfg = instance.join("instance.cfg");
+            journal.backup(&cfg).unwrap();
+            std::fs::write(&cfg, "new").unwrap();
+        }
+
+        assert!(!instance.exists());
+        assert_eq!(entries(instance.parent().unwrap()), ["Pack"]);
+    }
+
+    #[test]
+    fn commit_keeps_changes_and_drops_backups() {
+        let (_root, instance) = temp_instance();
+        let cfg = instance.join("instance.cfg");
+        let mut journal = InstallJournal::new(&instance);
+        journal.backup(&cfg).unwrap();
+        std::fs::write(&cfg, "changed").unwrap();
+        journal.commit();
+
+        assert_eq!(std::fs::read_to_string(&cfg).unwrap(), "changed");
+        assert_eq!(entries(instance.parent().unwrap()), ["Pack"]);
+    }
+}
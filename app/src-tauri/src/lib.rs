@@ -1,22 +1,76 @@
+mod assets;
+mod cache;
+pub mod cli;
 mod deletion_guard;
+mod download;
+mod github;
+pub mod installer;
+mod instance;
+mod journal;
+pub mod metadata;
 mod modpack;
+mod offline;
+mod pack;
+mod packwiz;
+mod pe;
+mod pkcs7;
 mod platform;
+mod preflight;
+pub mod progress;
+pub mod uninstall;
+#[cfg(test)]
+mod test_util;
 mod util;
-
-use std::path::PathBuf;
-
-use deletion_guard::TemporaryFileCleaner;
-use download_extract_progress::{download_github, extract_zip};
-use futures_util::{pin_mut, StreamExt};
-use modpack::install_modpack;
-use tauri::{AppHandle, Emitter, Manager};
-use tokio::process::Command;
-use util::ModpackConfig;
+pub mod verify;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use download::DownloadPolicy;
+use installer::RunningInstall;
+use modpack::InstallOptions;
+use pack::PackInfo;
+use progress::TauriProgress;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+use uninstall::UninstallReport;
+pub use util::ModpackConfig;
+use verify::{InstanceReport, IssueKind};
+
+/// The embedded config together with what the pack's `pack.toml` says about it
+#[derive(Serialize)]
+struct ConfigInfo {
+    #[serde(flatten)]
+    config: ModpackConfig,
+    #[serde(flatten)]
+    pack: Option<PackInfo>,
+    /// Why `pack.toml` couldn't be read, the installer still starts without it
+    pack_error: Option<String>,
+}
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
-fn read_config() -> Result<ModpackConfig, String> {
-    util::read_metadata()
+async fn read_config() -> Result<ConfigInfo, String> {
+    let config = util::read_metadata().map_err(|e| e.to_string())?;
+
+    // The window stays empty until this returns, so don't wait for slow servers as long as installs do
+    let policy = DownloadPolicy {
+        stall_timeout: Duration::from_secs(5),
+        ..DownloadPolicy::default()
+    };
+    let (pack, pack_error) = match pack::fetch(&config.packwiz_url, &policy).await {
+        Ok(pack) => (Some(PackInfo::from(&pack)), None),
+        Err(e) => {
+            log::warn!("Failed to read pack.toml: {:#}", e);
+            (None, Some(format!("{:#}", e)))
+        }
+    };
+
+    Ok(ConfigInfo {
+        config,
+        pack,
+        pack_error,
+    })
 }
 
 #[tauri::command]
@@ -30,151 +84,68 @@ fn get_prism_launcher_exec() -> Result<Option<PathBuf>, String> {
 }
 
 #[tauri::command]
-async fn install_portable(app: AppHandle, path: &str) -> Result<(), String> {
-    // Validate path
-    let path = std::path::Path::new(path);
-    if !path.exists() {
-        return Err("The specified path doesn't exist".into());
-    }
-
-    if !path.is_dir() {
-        return Err("The specified path is not a directory".into());
-    }
-
-    // Emit progress update
-    app.emit(
-        "install_progress",
-        (0.0, "Starting portable installation..."),
-    )
-    .unwrap();
-
-    // Installing PrismLauncher
-    let tmp_file = TemporaryFileCleaner::new();
-    let s = download_github(
-        "PrismLauncher/PrismLauncher",
-        |s| s.to_lowercase().contains("portable") && s.contains("MSVC") && !s.contains("arm64"),
-        tmp_file.file_path(),
-        None,
-    )
-    .await;
-
-    if let Err(e) = s {
-        return Err(format!("Failed to download PrismLauncher: {}", e));
-    }
-
-    let stream = s.unwrap();
-
-    pin_mut!(stream);
-    while let Some(res) = stream.next().await {
-        let (percentage, msg) =
-            res.map_err(|e| format!("Error downloading PrismLauncher: {}", e))?;
-
-        app.emit("install_progress", (percentage / 3.0, msg))
-            .unwrap();
-    }
-
-    app.emit("install_progress", (0.333, "Extracting PrismLauncher"))
-        .unwrap();
-
-    println!(
-        "Extracting PrismLauncher to: {}",
-        tmp_file.file_path().display()
-    );
-
-    // Extracting PrismLauncher
-    let extract = extract_zip(tmp_file.file_path(), path).await;
-    pin_mut!(extract);
-
-    while let Some(res) = extract.next().await {
-        let (percentage, msg) =
-            res.map_err(|e| format!("Error extracting PrismLauncher: {}", e))?;
-
-        app.emit("install_progress", (0.333 + percentage / 3.0, msg))
-            .unwrap();
-    }
-
-    let install = install_modpack(path, &path.join("prismlauncher.exe"));
-    pin_mut!(install);
-
-    while let Some(res) = install.next().await {
-        let (percentage, msg) = res.map_err(|e| format!("Error installing modpack: {}", e))?;
-        app.emit("install_progress", (0.666 + percentage / 3.0, msg))
-            .unwrap();
-    }
-    Ok(())
+async fn install_portable(
+    app: AppHandle,
+    running: State<'_, RunningInstall>,
+    path: &str,
+) -> Result<(), String> {
+    let progress = TauriProgress::new(app);
+    running
+        .run(installer::install_portable(
+            Path::new(path),
+            InstallOptions::default(),
+            &progress,
+        ))
+        .await
 }
 
 #[tauri::command]
-// Custom path is not supported for linux
 async fn use_or_install_launcher(
     app: AppHandle,
+    running: State<'_, RunningInstall>,
     custom_path: Option<PathBuf>,
 ) -> Result<(), String> {
-    let path = custom_path.or(get_prism_launcher_exec().ok().flatten());
-
-    log::info!("PrismLauncher path: {:?}", path);
-    if path.is_none() {
-        let tmp_file = TemporaryFileCleaner::new();
-        let s = download_github(
-            "PrismLauncher/PrismLauncher",
-            |s| s.contains(".exe") && s.contains("MSVC") && !s.contains("arm64"),
-            tmp_file.file_path(),
-            None,
-        )
-        .await;
-
-        if let Err(e) = s {
-            return Err(format!("Failed to download PrismLauncher: {}", e));
-        }
-
-        let stream = s.unwrap();
-
-        pin_mut!(stream);
-        while let Some(res) = stream.next().await {
-            let (percentage, msg) =
-                res.map_err(|e| format!("Error downloading PrismLauncher: {}", e))?;
-            app.emit("install_progress", (percentage / 3.0, msg))
-                .unwrap();
-        }
-
-        app.emit("install_progress", (0.333, "Installing PrismLauncher"))
-            .unwrap();
-
-        let out = Command::new(&tmp_file.file_path())
-            .output()
-            .await
-            .map_err(|e| format!("Failed to run PrismLauncher installer: {}", e))?;
-
-        let status = out.status;
-        if !status.success() {
-            return Err(format!(
-                "PrismLauncher installer exited with code: {}",
-                status.code().unwrap_or(-1)
-            ));
-        }
-
-        app.emit("install_progress", (0.666, "Installing modpack..."))
-            .unwrap();
-    }
+    let progress = TauriProgress::new(app);
+    running
+        .run(installer::use_or_install_launcher(
+            custom_path,
+            InstallOptions::default(),
+            &progress,
+        ))
+        .await
+}
 
-    let path = path.or(get_prism_launcher_exec().ok().flatten());
-    if path.is_none() {
-        return Err("PrismLauncher installation canceled.".into());
-    }
+#[tauri::command]
+async fn uninstall(
+    data_dir: Option<PathBuf>,
+    backup_dir: Option<PathBuf>,
+) -> Result<UninstallReport, String> {
+    installer::uninstall(data_dir.as_deref(), None, backup_dir.as_deref())
+}
 
-    let path = path.unwrap();
-    let path = std::path::Path::new(&path);
+#[tauri::command]
+async fn verify_instance(data_dir: Option<PathBuf>) -> Result<Vec<InstanceReport>, String> {
+    installer::verify(data_dir.as_deref(), None)
+}
 
-    //TODO work on linux support (don't know if I will do that like ever who uses that on linux if they even can't install a modpack themselves??)
-    let install = install_modpack(path.parent().unwrap(), path);
-    pin_mut!(install);
+#[tauri::command]
+async fn repair_instance(
+    data_dir: Option<PathBuf>,
+    instance: Option<PathBuf>,
+    kinds: Option<Vec<IssueKind>>,
+) -> Result<Vec<InstanceReport>, String> {
+    installer::repair(
+        data_dir.as_deref(),
+        None,
+        instance.as_deref(),
+        kinds.as_deref(),
+    )
+    .await
+}
 
-    while let Some(res) = install.next().await {
-        let (percentage, msg) = res.map_err(|e| format!("Error installing modpack: {}", e))?;
-        app.emit("install_progress", (0.666 + percentage / 3.0, msg))
-            .unwrap();
-    }
-    Ok(())
+#[tauri::command]
+fn cancel_install(running: State<'_, RunningInstall>) -> bool {
+    running.cancel()
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -192,7 +163,16 @@ pub fn run() {
         )
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
+        .manage(RunningInstall::default())
         .setup(|app| {
+            if let Ok(cli::Command::Window { ignored }) =
+                cli::parse_args(std::env::args().skip(1).collect())
+            {
+                if !ignored.is_empty() {
+                    log::info!("Ignoring unknown arguments {:?}", ignored);
+                }
+            }
+
             let config = util::read_metadata().map_err(|e| {
                 log::error!("Failed to read config: {}", e);
                 e
@@ -209,7 +189,11 @@ pub fn run() {
             get_prism_launcher_data,
             get_prism_launcher_exec,
             use_or_install_launcher,
-            install_portable
+            install_portable,
+            cancel_install,
+            uninstall,
+            verify_instance,
+            repair_instance
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
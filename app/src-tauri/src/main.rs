@@ -1,9 +1,10 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-#[cfg(not(target_os = "windows"))]
-compile_error!("Only Windows is supported for now");
-
 fn main() {
+    if let Some(code) = modpack_installer_lib::cli::run() {
+        std::process::exit(code);
+    }
+
     modpack_installer_lib::run()
 }
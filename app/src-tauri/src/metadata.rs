@@ -0,0 +1,586 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use sha2::{Digest, Sha256};
+
+use crate::pe;
+use crate::pkcs7;
+
+/// Marks the end of a versioned metadata trailer
+pub const MAGIC: &[u8; 8] = b"PWZLMETA";
+/// Current version of the trailer layout written by `encode_trailer`
+pub const FORMAT_VERSION: u32 = 1;
+
+/// OID `2.25.269969011283810079518339104717726291198` of the unsigned signer attribute holding
+/// the trailer of signed installers, derived from a UUID so it can't clash with anyone else's
+const SIGNATURE_ATTRIBUTE: &[u8] = &[
+    0x69, 0x83, 0x96, 0x9a, 0x91, 0xfa, 0xbe, 0xfa, 0xea, 0x8c, 0x93, 0x9b, 0xe9, 0xcb, 0xce, 0xcb,
+    0xdb, 0xd0, 0xa9, 0x7e,
+];
+
+const HASH_LEN: u64 = 32;
+/// sha256 + payload length (u64) + version (u32) + magic
+const FOOTER_LEN: u64 = HASH_LEN + 8 + 4 + MAGIC.len() as u64;
+
+#[derive(Debug)]
+pub enum MetadataError {
+    /// The binary has no metadata appended to it
+    NotEmbedded,
+    /// A trailer was found, but its contents don't check out
+    Corrupted(String),
+    /// The trailer was written by a newer version of the embedding tool
+    UnsupportedVersion(u32),
+    /// The metadata is covered by the code signature and can't be changed without re-signing
+    Signed,
+    Io(std::io::Error),
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataError::NotEmbedded => write!(f, "No modpack metadata is embedded in this installer"),
+            MetadataError::Corrupted(e) => write!(f, "Embedded modpack metadata is corrupted: {}", e),
+            MetadataError::UnsupportedVersion(v) => {
+                write!(f, "Embedded modpack metadata has unsupported format version {}", v)
+            }
+            MetadataError::Signed => write!(
+                f,
+                "Embedded modpack metadata is covered by the code signature, re-sign the installer after changing it"
+            ),
+            MetadataError::Io(e) => write!(f, "Failed to read embedded modpack metadata: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {}
+
+impl From<std::io::Error> for MetadataError {
+    fn from(e: std::io::Error) -> Self {
+        MetadataError::Io(e)
+    }
+}
+
+/// Where a trailer is stored in the binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// Appended to the file, starting at this offset
+    Appended(u64),
+    /// In an unsigned attribute of the Authenticode signature
+    Signature,
+}
+
+#[derive(Debug)]
+pub struct Trailer {
+    pub payload: Vec<u8>,
+    pub location: Location,
+    /// Layout version, 0 for the legacy `[json][u64 len]` layout
+    pub version: u32,
+}
+
+/// Builds the trailer that has to be appended to a binary to embed `payload`
+///
+/// Layout: `[payload][sha256(payload)][payload len: u64 LE][version: u32 LE][MAGIC]`
+pub fn encode_trailer(payload: &[u8]) -> Vec<u8> {
+    let mut trailer = Vec::with_capacity(payload.len() + FOOTER_LEN as usize);
+    trailer.extend_from_slice(payload);
+    trailer.extend_from_slice(&Sha256::digest(payload));
+    trailer.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    trailer.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    trailer.extend_from_slice(MAGIC);
+
+    trailer
+}
+
+/// Reads the trailer whose last byte is right before `end`
+pub fn read_trailer<R: Read + Seek>(reader: &mut R, end: u64) -> Result<Trailer, MetadataError> {
+    read_trailer_checked(reader, end, true)
+}
+
+/// Like [`read_trailer`], but without `checked` a trailer with a bad checksum or an unknown
+/// version is read as it is, so it can still be stripped by its recorded length
+fn read_trailer_checked<R: Read + Seek>(
+    reader: &mut R,
+    end: u64,
+    checked: bool,
+) -> Result<Trailer, MetadataError> {
+    if end >= FOOTER_LEN {
+        reader.seek(SeekFrom::Start(end - FOOTER_LEN))?;
+        let mut footer = [0u8; FOOTER_LEN as usize];
+        reader.read_exact(&mut footer)?;
+
+        let (hash, rest) = footer.split_at(HASH_LEN as usize);
+        let (length, rest) = rest.split_at(8);
+        let (version, magic) = rest.split_at(4);
+
+        if magic == MAGIC {
+            let version = u32::from_le_bytes(version.try_into().unwrap());
+            if checked && version != FORMAT_VERSION {
+                return Err(MetadataError::UnsupportedVersion(version));
+            }
+
+            let length = u64::from_le_bytes(length.try_into().unwrap());
+            if length > end - FOOTER_LEN {
+                return Err(MetadataError::Corrupted(format!(
+                    "payload length {} exceeds file size",
+                    length
+                )));
+            }
+
+            let start = end - FOOTER_LEN - length;
+            let mut payload = vec![0u8; length as usize];
+            reader.seek(SeekFrom::Start(start))?;
+            reader.read_exact(&mut payload)?;
+
+            if checked && Sha256::digest(&payload).as_slice() != hash {
+                return Err(MetadataError::Corrupted("checksum mismatch".to_string()));
+            }
+
+            return Ok(Trailer {
+                payload,
+                location: Location::Appended(start),
+                version,
+            });
+        }
+    }
+
+    read_legacy_trailer(reader, end)
+}
+
+/// Reads the unversioned `[json][u64 len]` layout written by older embedding scripts
+fn read_legacy_trailer<R: Read + Seek>(reader: &mut R, end: u64) -> Result<Trailer, MetadataError> {
+    if end < 8 {
+        return Err(MetadataError::NotEmbedded);
+    }
+
+    reader.seek(SeekFrom::Start(end - 8))?;
+    let mut length_bytes = [0u8; 8];
+    reader.read_exact(&mut length_bytes)?;
+
+    let length = u64::from_le_bytes(length_bytes);
+    if length == 0 || length > end - 8 {
+        return Err(MetadataError::NotEmbedded);
+    }
+
+    let start = end - 8 - length;
+    let mut payload = vec![0u8; length as usize];
+    reader.seek(SeekFrom::Start(start))?;
+    reader.read_exact(&mut payload)?;
+
+    // Without a marker, the only hint that this is our payload is that it looks like a JSON object
+    if payload.first() != Some(&b'{') {
+        return Err(MetadataError::NotEmbedded);
+    }
+
+    Ok(Trailer {
+        payload,
+        location: Location::Appended(start),
+        version: 0,
+    })
+}
+
+/// Reads the trailer stored in the signature of a signed binary
+fn read_signature_trailer(
+    file: &mut File,
+    table: &pe::CertificateTable,
+    checked: bool,
+) -> Result<Trailer, MetadataError> {
+    let signature = pe::read_signature(file, table)?;
+    let value = match pkcs7::unsigned_attribute(&signature, SIGNATURE_ATTRIBUTE) {
+        Ok(Some(value)) => value,
+        Ok(None) => return Err(MetadataError::NotEmbedded),
+        Err(e) => {
+            log::warn!("Couldn't read the code signature: {}", e);
+            return Err(MetadataError::NotEmbedded);
+        }
+    };
+
+    let mut trailer = read_trailer_checked(&mut Cursor::new(&value), value.len() as u64, checked)?;
+    trailer.location = Location::Signature;
+    Ok(trailer)
+}
+
+/// Finds the metadata trailer of `file`
+///
+/// Besides the end of the file, this looks inside the Authenticode signature (where
+/// `append_trailer` puts it for signed binaries) and right before the certificate table, for
+/// binaries that were signed after the metadata had been appended.
+pub fn find_trailer(file: &mut File) -> Result<Trailer, MetadataError> {
+    find_trailer_checked(file, true)
+}
+
+fn find_trailer_checked(file: &mut File, checked: bool) -> Result<Trailer, MetadataError> {
+    let len = file.metadata()?.len();
+    let table = pe::certificate_table(file)?;
+
+    // Data after the certificate table is covered by the signature, so it's checked first to
+    // strip it before anything else
+    if table.is_none_or(|t| t.end() < len) {
+        match read_trailer_checked(file, len, checked) {
+            Err(MetadataError::NotEmbedded) => {}
+            res => return res,
+        }
+    }
+
+    let Some(table) = table else {
+        return Err(MetadataError::NotEmbedded);
+    };
+    match read_signature_trailer(file, &table, checked) {
+        Err(MetadataError::NotEmbedded) => {}
+        res => return res,
+    }
+
+    // The certificate table is 8-byte aligned, so there may be zero padding before it
+    for end in (0..8).filter_map(|pad| table.offset.checked_sub(pad)) {
+        match read_trailer_checked(file, end, checked) {
+            Err(MetadataError::NotEmbedded) => continue,
+            res => return res,
+        }
+    }
+
+    Err(MetadataError::NotEmbedded)
+}
+
+/// Embeds `payload` as a new trailer into `file`
+///
+/// If the binary is signed, the trailer goes into an unsigned attribute of the signature so the
+/// signature stays valid, even with `EnableCertPaddingCheck`. Otherwise it's appended.
+pub fn append_trailer(file: &mut File, payload: &[u8]) -> Result<(), MetadataError> {
+    let len = file.metadata()?.len();
+    let trailer = encode_trailer(payload);
+
+    match pe::certificate_table(file)? {
+        Some(table) if table.end() == len => {
+            let signature = pe::read_signature(file, &table)?;
+            let signature =
+                pkcs7::set_unsigned_attribute(&signature, SIGNATURE_ATTRIBUTE, Some(&trailer))?;
+            pe::replace_signature(file, &table, &signature)?;
+            log::info!("Embedding metadata into the code signature");
+        }
+        table => {
+            if table.is_some() {
+                log::warn!("Data follows the certificate table, appending metadata after it");
+            }
+            file.seek(SeekFrom::End(0))?;
+            file.write_all(&trailer)?;
+            file.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes every trailer of `file`, returning how many were stripped
+///
+/// Older embedding scripts appended a new payload on every run, so there may be more than one.
+/// Trailers with a bad checksum or an unknown version are stripped too, as long as their
+/// recorded length fits the file.
+pub fn strip_trailers(file: &mut File) -> Result<usize, MetadataError> {
+    let mut stripped = 0;
+    loop {
+        let trailer = match find_trailer(file) {
+            Ok(trailer) => trailer,
+            Err(MetadataError::NotEmbedded) => return Ok(stripped),
+            Err(e @ (MetadataError::Corrupted(_) | MetadataError::UnsupportedVersion(_))) => {
+                log::warn!("{}, stripping it anyway", e);
+                find_trailer_checked(file, false)?
+            }
+            Err(e) => return Err(e),
+        };
+
+        let table = pe::certificate_table(file)?;
+        match (trailer.location, table) {
+            (Location::Signature, Some(table)) => {
+                let signature = pe::read_signature(file, &table)?;
+                let signature =
+                    pkcs7::set_unsigned_attribute(&signature, SIGNATURE_ATTRIBUTE, None)?;
+                pe::replace_signature(file, &table, &signature)?;
+            }
+            (Location::Appended(start), Some(table)) if start < table.end() => {
+                return Err(MetadataError::Signed)
+            }
+            (Location::Appended(start), _) => file.set_len(start)?,
+            (Location::Signature, None) => unreachable!("signature trailers need a signature"),
+        }
+        stripped += 1;
+    }
+}
+
+/// Replaces every trailer of `file` with one holding `payload`, returning how many were stripped
+pub fn replace_trailer(file: &mut File, payload: &[u8]) -> Result<usize, MetadataError> {
+    let stripped = strip_trailers(file)?;
+    append_trailer(file, payload)?;
+    Ok(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+
+    use super::*;
+    use crate::deletion_guard::TemporaryFileCleaner;
+
+    const SIGNED: &[u8] = include_bytes!("../tests/fixtures/signed.exe");
+    const UNSIGNED: &[u8] = include_bytes!("../tests/fixtures/unsigned.exe");
+    const PAYLOAD: &[u8] = br#"{"name":"Fixture"}"#;
+
+    /// A copy of `contents` in a temporary file, opened for reading and writing
+    fn binary(contents: &[u8]) -> (TemporaryFileCleaner, File) {
+        let tmp = TemporaryFileCleaner::new_with_extension("exe");
+        std::fs::write(tmp.file_path(), contents).unwrap();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(tmp.file_path())
+            .unwrap();
+        (tmp, file)
+    }
+
+    fn legacy_trailer(payload: &[u8]) -> Vec<u8> {
+        [payload, &(payload.len() as u64).to_le_bytes()].concat()
+    }
+
+    #[test]
+    fn nothing_is_embedded_in_fixtures() {
+        for fixture in [SIGNED, UNSIGNED] {
+            let (_tmp, mut file) = binary(fixture);
+            assert!(matches!(
+                find_trailer(&mut file),
+                Err(MetadataError::NotEmbedded)
+            ));
+            assert_eq!(strip_trailers(&mut file).unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn appends_to_unsigned_binaries() {
+        let (tmp, mut file) = binary(UNSIGNED);
+        append_trailer(&mut file, PAYLOAD).unwrap();
+
+        let trailer = find_trailer(&mut file).unwrap();
+        assert_eq!(trailer.payload, PAYLOAD);
+        assert_eq!(trailer.version, FORMAT_VERSION);
+        assert_eq!(trailer.location, Location::Appended(UNSIGNED.len() as u64));
+
+        assert_eq!(strip_trailers(&mut file).unwrap(), 1);
+        assert_eq!(std::fs::read(tmp.file_path()).unwrap(), UNSIGNED);
+    }
+
+    #[test]
+    fn embeds_into_signature_of_signed_binaries() {
+        let (tmp, mut file) = binary(SIGNED);
+        append_trailer(&mut file, PAYLOAD).unwrap();
+
+        let trailer = find_trailer(&mut file).unwrap();
+        assert_eq!(trailer.payload, PAYLOAD);
+        assert_eq!(trailer.location, Location::Signature);
+
+        // Everything Authenticode hashes stays the same, only the table size changes
+        let table = pe::certificate_table(&mut file).unwrap().unwrap();
+        let embedded = std::fs::read(tmp.file_path()).unwrap();
+        let size_field = table.directory_entry as usize + 4..table.directory_entry as usize + 8;
+        assert_eq!(embedded[..size_field.start], SIGNED[..size_field.start]);
+        assert_eq!(embedded[size_field.end..512], SIGNED[size_field.end..512]);
+        assert_eq!(table.end(), embedded.len() as u64);
+
+        assert_eq!(strip_trailers(&mut file).unwrap(), 1);
+        assert_eq!(std::fs::read(tmp.file_path()).unwrap(), SIGNED);
+    }
+
+    #[test]
+    fn replaces_signature_trailer() {
+        let (_tmp, mut file) = binary(SIGNED);
+        append_trailer(&mut file, PAYLOAD).unwrap();
+        assert_eq!(strip_trailers(&mut file).unwrap(), 1);
+        append_trailer(&mut file, br#"{"name":"Replaced"}"#).unwrap();
+
+        assert_eq!(
+            find_trailer(&mut file).unwrap().payload,
+            br#"{"name":"Replaced"}"#
+        );
+    }
+
+    #[test]
+    fn strips_trailer_appended_after_certificate_table() {
+        // What embedding by appending did to signed binaries, which broke their signature
+        let (tmp, mut file) = binary(&[SIGNED, &encode_trailer(PAYLOAD)].concat());
+
+        let trailer = find_trailer(&mut file).unwrap();
+        assert_eq!(trailer.location, Location::Appended(SIGNED.len() as u64));
+        assert_eq!(strip_trailers(&mut file).unwrap(), 1);
+        assert_eq!(std::fs::read(tmp.file_path()).unwrap(), SIGNED);
+    }
+
+    #[test]
+    fn finds_trailer_of_binaries_signed_after_embedding() {
+        // Insert the trailer and alignment padding between the image and the certificate table
+        let mut trailer = encode_trailer(PAYLOAD);
+        trailer.resize(trailer.len().next_multiple_of(8), 0);
+        let mut contents = [&SIGNED[..512], &trailer, &SIGNED[512..]].concat();
+        let table = pe::certificate_table(&mut Cursor::new(SIGNED))
+            .unwrap()
+            .unwrap();
+        let offset = (512 + trailer.len()) as u32;
+        let entry = table.directory_entry as usize;
+        contents[entry..entry + 4].copy_from_slice(&offset.to_le_bytes());
+
+        let (_tmp, mut file) = binary(&contents);
+        let found = find_trailer(&mut file).unwrap();
+        assert_eq!(found.payload, PAYLOAD);
+        assert_eq!(found.location, Location::Appended(512));
+        assert!(matches!(
+            strip_trailers(&mut file),
+            Err(MetadataError::Signed)
+        ));
+    }
+
+    #[test]
+    fn rejects_corrupt_hash() {
+        let mut trailer = encode_trailer(PAYLOAD);
+        trailer[1] ^= 0xff;
+        let (_tmp, mut file) = binary(&[UNSIGNED, &trailer].concat());
+
+        assert!(matches!(
+            find_trailer(&mut file),
+            Err(MetadataError::Corrupted(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_corrupt_hash_in_signature() {
+        let mut trailer = encode_trailer(PAYLOAD);
+        trailer[1] ^= 0xff;
+        let (_tmp, mut file) = binary(SIGNED);
+        let table = pe::certificate_table(&mut file).unwrap().unwrap();
+        let signature = pe::read_signature(&mut file, &table).unwrap();
+        let signature =
+            pkcs7::set_unsigned_attribute(&signature, SIGNATURE_ATTRIBUTE, Some(&trailer)).unwrap();
+        pe::replace_signature(&mut file, &table, &signature).unwrap();
+
+        assert!(matches!(
+            find_trailer(&mut file),
+            Err(MetadataError::Corrupted(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_oversized_length() {
+        let mut trailer = encode_trailer(PAYLOAD);
+        let length = trailer.len() - FOOTER_LEN as usize + HASH_LEN as usize;
+        trailer[length..length + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let (_tmp, mut file) = binary(&[UNSIGNED, &trailer].concat());
+
+        assert!(matches!(
+            find_trailer(&mut file),
+            Err(MetadataError::Corrupted(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let mut trailer = encode_trailer(PAYLOAD);
+        let version = trailer.len() - MAGIC.len() - 4;
+        trailer[version..version + 4].copy_from_slice(&2u32.to_le_bytes());
+        let (_tmp, mut file) = binary(&[UNSIGNED, &trailer].concat());
+
+        assert!(matches!(
+            find_trailer(&mut file),
+            Err(MetadataError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn reads_legacy_trailer() {
+        let contents = [UNSIGNED, &legacy_trailer(PAYLOAD)].concat();
+        let trailer =
+            read_legacy_trailer(&mut Cursor::new(&contents), contents.len() as u64).unwrap();
+        assert_eq!(trailer.payload, PAYLOAD);
+        assert_eq!(trailer.version, 0);
+        assert_eq!(trailer.location, Location::Appended(UNSIGNED.len() as u64));
+    }
+
+    #[test]
+    fn legacy_trailer_needs_plausible_json() {
+        let cases = [
+            legacy_trailer(b"not json"),
+            [&[0u8; 4][..], &u64::MAX.to_le_bytes()].concat(),
+            0u64.to_le_bytes().to_vec(),
+            vec![1, 2, 3],
+        ];
+        for case in cases {
+            let contents = [UNSIGNED, &case].concat();
+            let res = read_legacy_trailer(&mut Cursor::new(&contents), contents.len() as u64);
+            assert!(matches!(res, Err(MetadataError::NotEmbedded)));
+        }
+    }
+
+    #[test]
+    fn strips_every_legacy_trailer() {
+        let contents = [
+            UNSIGNED,
+            &legacy_trailer(PAYLOAD),
+            &legacy_trailer(br#"{"name":"Second"}"#),
+            &encode_trailer(br#"{"name":"Third"}"#),
+        ]
+        .concat();
+        let (tmp, mut file) = binary(&contents);
+
+        assert_eq!(
+            find_trailer(&mut file).unwrap().payload,
+            br#"{"name":"Third"}"#
+        );
+        assert_eq!(strip_trailers(&mut file).unwrap(), 3);
+        assert_eq!(std::fs::read(tmp.file_path()).unwrap(), UNSIGNED);
+    }
+
+    #[test]
+    fn replaces_damaged_trailers() {
+        let mut corrupt = encode_trailer(PAYLOAD);
+        corrupt[1] ^= 0xff;
+        let mut unknown = encode_trailer(PAYLOAD);
+        let version = unknown.len() - MAGIC.len() - 4;
+        unknown[version..version + 4].copy_from_slice(&2u32.to_le_bytes());
+
+        for trailer in [corrupt, unknown] {
+            let (tmp, mut file) = binary(&[UNSIGNED, &trailer].concat());
+            assert_eq!(
+                replace_trailer(&mut file, br#"{"name":"Repaired"}"#).unwrap(),
+                1
+            );
+            assert_eq!(
+                find_trailer(&mut file).unwrap().payload,
+                br#"{"name":"Repaired"}"#
+            );
+            assert_eq!(strip_trailers(&mut file).unwrap(), 1);
+            assert_eq!(std::fs::read(tmp.file_path()).unwrap(), UNSIGNED);
+        }
+    }
+
+    #[test]
+    fn strips_corrupt_trailer_in_signature() {
+        let mut trailer = encode_trailer(PAYLOAD);
+        trailer[1] ^= 0xff;
+        let (tmp, mut file) = binary(SIGNED);
+        let table = pe::certificate_table(&mut file).unwrap().unwrap();
+        let signature = pe::read_signature(&mut file, &table).unwrap();
+        let signature =
+            pkcs7::set_unsigned_attribute(&signature, SIGNATURE_ATTRIBUTE, Some(&trailer)).unwrap();
+        pe::replace_signature(&mut file, &table, &signature).unwrap();
+
+        assert_eq!(strip_trailers(&mut file).unwrap(), 1);
+        assert_eq!(std::fs::read(tmp.file_path()).unwrap(), SIGNED);
+    }
+
+    #[test]
+    fn keeps_trailers_whose_length_does_not_fit() {
+        let mut trailer = encode_trailer(PAYLOAD);
+        let length = trailer.len() - FOOTER_LEN as usize + HASH_LEN as usize;
+        trailer[length..length + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let (_tmp, mut file) = binary(&[UNSIGNED, &trailer].concat());
+
+        assert!(matches!(
+            replace_trailer(&mut file, PAYLOAD),
+            Err(MetadataError::Corrupted(_))
+        ));
+    }
+}
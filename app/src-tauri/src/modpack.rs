@@ -4,43 +4,85 @@ use std::process::Command;
 use async_stream::stream;
 use configparser::ini::Ini;
 use configparser::ini::WriteOptions;
-use download_extract_progress::{download, extract_zip};
+use download_extract_progress::extract_zip;
 use futures_core::Stream;
 use futures_util::pin_mut;
 use futures_util::StreamExt;
 use image::ImageReader;
-use tokio::fs;
 use url::Url;
 use uuid::Uuid;
 
+use crate::cache;
 use crate::deletion_guard::TemporaryFileCleaner;
-use crate::platform::create_shortcut;
-use crate::util;
+use crate::download::{verify_sha256, DownloadPolicy};
+use crate::instance::{self, InstallManifest, MANIFEST_FILE};
+use crate::journal::InstallJournal;
+use crate::offline::OfflinePayload;
+use crate::pack;
+use crate::packwiz::{sync_mods, ModSync};
+use crate::platform::{create_shortcut, shortcut_paths, UserPaths};
+use crate::progress::{Phase, ProgressEvent};
+use crate::util::ModpackConfig;
+
+/// Runs packwiz before every launch to keep the instance in sync with the pack
+pub const PACKWIZ_BOOTSTRAP: &[u8] = include_bytes!("./packwiz_bootstrap.jar");
+
+pub fn prelaunch_command(packwiz_url: &str) -> String {
+    format!("$INST_JAVA -jar packwiz_bootstrap.jar {}", packwiz_url)
+}
+
+/// How an install should treat the launcher and existing instances
+#[derive(Debug, Clone)]
+pub struct InstallOptions {
+    /// Start PrismLauncher with the instance once it's installed
+    pub launch: bool,
+    /// Update a previous install of the same modpack instead of creating "Name (n)"
+    pub update_existing: bool,
+    /// Retries and timeouts of the launcher, modpack and icon downloads
+    pub download: DownloadPolicy,
+    /// Where the launcher, shortcuts and download cache go
+    pub paths: UserPaths,
+}
+
+impl Default for InstallOptions {
+    fn default() -> Self {
+        Self {
+            launch: true,
+            update_existing: true,
+            download: DownloadPolicy::default(),
+            paths: UserPaths::current(),
+        }
+    }
+}
 
 pub fn install_modpack(
     prism_data: &Path,
     prism_exec: &Path,
-) -> impl Stream<Item = Result<(f32, String), anyhow::Error>> {
+    config: ModpackConfig,
+    options: InstallOptions,
+) -> impl Stream<Item = Result<ProgressEvent, anyhow::Error>> {
     let prism_data = prism_data.to_owned();
     let prism_exec = prism_exec.to_owned();
     stream! {
         log::info!("Starting modpack installation");
-        yield Ok((0.0, "Importing instance".to_string()));
+        yield Ok(ProgressEvent::new(Phase::DownloadPack, 0.0, "Importing instance"));
 
-        let config = util::read_metadata()
-            .map_err(|e| anyhow::anyhow!("Failed to get modpack config: {}", e));
-        if let Err(e) = config {
-            log::error!("Failed to get modpack config: {}", e);
-            yield Err(e);
-            return;
+        match config.base_pack() {
+            Some(url) => log::info!("Using modpack config with base URL: {}", url),
+            None => log::info!("Using modpack config without base pack"),
         }
 
-        let config = config.unwrap();
-        log::info!("Using modpack config with base URL: {}", config.base_pack_url);
-
         let instances_dir = prism_data.join("instances");
+        let previous = options
+            .update_existing
+            .then(|| instance::find_previous_install(&instances_dir, &config))
+            .flatten();
+
         let mut instance_dir = instances_dir.join(&config.name);
-        if instance_dir.exists() {
+        if let Some(previous) = &previous {
+            log::info!("Updating existing instance at {}", previous.dir.display());
+            instance_dir = previous.dir.clone();
+        } else if instance_dir.exists() {
             let mut found = false;
             for i in 0..50 {
                 let new_instance_dir = instance_dir.with_file_name(format!("{} ({})", config.name, i));
@@ -58,55 +100,139 @@ pub fn install_modpack(
             }
         }
 
+        // Every change from here on is undone if the install doesn't finish
+        let mut journal = InstallJournal::new(&instance_dir);
+        journal.track_dir(&instance_dir);
+
+        // Updates extract into a staging directory first, so player data can be skipped
+        let staging = TemporaryFileCleaner::new_with_extension("extract");
+        let extract_dir = match previous {
+            Some(_) => staging.file_path().clone(),
+            None => instance_dir.clone(),
+        };
+
         let tmp_file = TemporaryFileCleaner::new();
-        let download_str = download(
-            format!("Modpack: {}", config.name).as_str(),
-            &config.base_pack_url,
-            tmp_file.file_path(),
-            None
-        ).await;
-
-        pin_mut!(download_str);
-        while let Some(res) = download_str.next().await {
-            if let Err(e) = res {
-                log::error!("Error downloading modpack: {}", e);
-                yield Err(anyhow::anyhow!("Error downloading modpack: {}", e));
+        let payload = OfflinePayload::locate();
+        let bundled = payload.as_ref().and_then(|p| p.extract_base_pack(tmp_file.file_path()));
+        let base_pack = match (bundled, config.base_pack()) {
+            (Some(Ok(_)), _) => {
+                let msg = format!("Using bundled modpack: {}", config.name);
+                yield Ok(ProgressEvent::new(Phase::DownloadPack, 1.0, msg));
+                true
+            }
+            (Some(Err(e)), _) => {
+                log::error!("Error extracting bundled modpack: {}", e);
+                yield Err(anyhow::anyhow!("Error extracting bundled modpack: {}", e));
                 return;
             }
+            (None, Some(url)) => {
+                let download_str = cache::fetch(
+                    options.paths.cache.as_deref(),
+                    url,
+                    tmp_file.file_path(),
+                    &options.download,
+                    config.base_pack_sha256.as_deref(),
+                );
+
+                pin_mut!(download_str);
+                while let Some(res) = download_str.next().await {
+                    if let Err(e) = res {
+                        log::error!("Error downloading modpack: {}", e);
+                        yield Err(anyhow::anyhow!("Error downloading modpack: {}", e));
+                        return;
+                    }
+
+                    let msg = format!("Downloading modpack: {}", config.name);
+                    yield Ok(ProgressEvent::download(Phase::DownloadPack, res.unwrap(), msg));
+                }
+                true
+            }
+            (None, None) => false,
+        };
+
+        if base_pack {
+            if let Some(expected) = &config.base_pack_sha256 {
+                if let Err(e) = verify_sha256(tmp_file.file_path(), expected, "the base pack") {
+                    log::error!("{}", e);
+                    yield Err(e);
+                    return;
+                }
+            }
 
-            let (percentage, msg) = res.unwrap();
-            yield Ok((percentage / 3.0, msg));
-        }
+            let extract_str = extract_zip(
+                tmp_file.file_path(),
+                &extract_dir
+            ).await;
+
+            pin_mut!(extract_str);
+            while let Some(res) = extract_str.next().await {
+                if let Err(e) = res {
+                    log::error!("Error extracting modpack: {}", e);
+                    yield Err(anyhow::anyhow!("Error extracting modpack: {}", e));
+                    return;
+                }
 
-        let extract_str = extract_zip(
-            &tmp_file.file_path(),
-            &instance_dir
-        ).await;
+                let (percentage, msg) = res.unwrap();
+                yield Ok(ProgressEvent::new(Phase::ExtractPack, percentage, msg));
+            }
+        } else {
+            // Without a base pack the instance is built from the versions in pack.toml
+            yield Ok(ProgressEvent::new(Phase::DownloadPack, 0.5, "Reading pack.toml"));
+            let pack = match pack::fetch(&config.packwiz_url, &options.download).await {
+                Ok(pack) => pack,
+                Err(e) => {
+                    log::error!("Failed to read pack.toml: {:#}", e);
+                    yield Err(anyhow::anyhow!("Failed to read pack.toml: {:#}", e));
+                    return;
+                }
+            };
 
-        pin_mut!(extract_str);
-        while let Some(res) = extract_str.next().await {
-            if let Err(e) = res {
-                log::error!("Error extracting modpack: {}", e);
-                yield Err(anyhow::anyhow!("Error extracting modpack: {}", e));
+            if let Err(e) = instance::generate(&extract_dir, &config.name, &pack) {
+                log::error!("Failed to generate instance: {}", e);
+                yield Err(anyhow::anyhow!("Failed to generate instance: {}", e));
                 return;
             }
 
-            let (percentage, msg) = res.unwrap();
-            yield Ok((0.333 + percentage / 3.0, msg));
+            let msg = format!("Created instance for Minecraft {}", pack.versions.minecraft);
+            yield Ok(ProgressEvent::new(Phase::ExtractPack, 1.0, msg));
+        }
+
+        let base_files = if previous.is_some() {
+            yield Ok(ProgressEvent::new(Phase::ExtractPack, 1.0, "Updating instance files"));
+            let previous_files = previous
+                .as_ref()
+                .and_then(|p| p.manifest.as_ref())
+                .map(|m| m.files.as_slice())
+                .unwrap_or_default();
+            instance::update_files(&extract_dir, &instance_dir, previous_files, &mut journal)
+        } else {
+            instance::list_files(&instance_dir)
+        };
+
+        // Hashes of everything that came from the base pack, recorded in the install manifest
+        let base_files = base_files.and_then(|files| instance::hash_files(&extract_dir, &files));
+        if let Err(e) = base_files {
+            log::error!("Failed to record base pack files: {}", e);
+            yield Err(anyhow::anyhow!("Failed to record base pack files: {}", e));
+            return;
         }
 
+        let base_files = base_files.unwrap();
+        log::info!("Installed {} base pack files into {}", base_files.len(), instance_dir.display());
+
         // Downloading packwiz and setting custom commands
+        yield Ok(ProgressEvent::new(Phase::Configure, 0.0, "Configuring instance"));
         let mc_folder = instance_dir.join("minecraft");
         let packwiz_jar = mc_folder.join("packwiz_bootstrap.jar");
         if !mc_folder.exists() {
-            if let Err(e) = fs::create_dir_all(&mc_folder).await {
+            if let Err(e) = journal.create_dir_all(&mc_folder).await {
                 log::error!("Failed to create minecraft directory: {}", e);
                 yield Err(anyhow::anyhow!("Failed to create minecraft directory: {}", e));
                 return;
             }
         }
 
-        let write_packwiz = fs::write(&packwiz_jar, include_bytes!("./packwiz_bootstrap.jar"))
+        let write_packwiz = journal.write(&packwiz_jar, PACKWIZ_BOOTSTRAP)
             .await
             .map_err(|e| {
                 log::error!("Failed to write packwiz bootstrap jar: {}", e);
@@ -128,10 +254,7 @@ pub fn install_modpack(
             return;
         }
 
-        let cmd = format!(
-            "$INST_JAVA -jar packwiz_bootstrap.jar {}",
-            config.packwiz_url
-        );
+        let cmd = prelaunch_command(&config.packwiz_url);
 
         log::info!("Setting pre-launch command: '{}'", cmd);
         prism_config.set("General", "PreLaunchCommand", Some(cmd));
@@ -152,7 +275,7 @@ pub fn install_modpack(
         let parsed_url = parsed_url.unwrap();
         let file_name = parsed_url
             .path_segments()
-            .and_then(|segments| segments.last())
+            .and_then(|mut segments| segments.next_back())
             .ok_or_else(|| anyhow::anyhow!("Failed to get file name from logo URL"));
         if let Err(e) = file_name {
             log::error!("Failed to get file name from logo URL: {}", e);
@@ -171,39 +294,62 @@ pub fn install_modpack(
             return;
         }
 
-        let icon_orig_ext = icon_orig_ext.unwrap();
+        // The bundled logo is used instead of the download, so the file gets its extension
+        let icon_orig_ext = payload
+            .as_ref()
+            .and_then(|p| p.logo_extension())
+            .unwrap_or(icon_orig_ext.unwrap());
         let tmp_ico = TemporaryFileCleaner::new_with_extension(icon_orig_ext);
 
-        let icon_uuid  = Uuid::new_v4().to_string();
+        let icon_uuid = previous
+            .as_ref()
+            .and_then(|p| p.icon_key.clone())
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
         let icon_path = prism_data.join("icons").join(format!("{icon_uuid}.png"));
         if !icon_path.parent().unwrap().exists() {
-            if let Err(e) = fs::create_dir_all(icon_path.parent().unwrap()).await {
+            if let Err(e) = journal.create_dir_all(icon_path.parent().unwrap()).await {
                 log::error!("Failed to create icons directory: {}", e);
                 yield Err(anyhow::anyhow!("Failed to create icons directory: {}", e));
                 return;
             }
         }
 
-        let icon_str = download(
-            format!("Modpack Icon: {}", config.name).as_str(),
-            &config.logo_url,
-            tmp_ico.file_path(),
-            None
-        ).await;
-
-        pin_mut!(icon_str);
-        while let Some(res) = icon_str.next().await {
-            if let Err(e) = res {
-                log::error!("Error downloading modpack icon: {}", e);
-                yield Err(anyhow::anyhow!("Error downloading modpack icon: {}", e));
+        match payload.as_ref().and_then(|p| p.extract_logo(tmp_ico.file_path())) {
+            Some(Ok(_)) => {
+                let msg = format!("Using bundled modpack icon: {}", config.name);
+                yield Ok(ProgressEvent::new(Phase::DownloadIcon, 1.0, msg));
+            }
+            Some(Err(e)) => {
+                log::error!("Error extracting bundled modpack icon: {}", e);
+                yield Err(anyhow::anyhow!("Error extracting bundled modpack icon: {}", e));
                 return;
             }
-
-            let (percentage, msg) = res.unwrap();
-            yield Ok((0.666 + percentage / 3.0, msg));
+            None => {
+                let icon_str = cache::fetch(
+                    options.paths.cache.as_deref(),
+                    &config.logo_url,
+                    tmp_ico.file_path(),
+                    &options.download,
+                    None,
+                );
+
+                pin_mut!(icon_str);
+                while let Some(res) = icon_str.next().await {
+                    if let Err(e) = res {
+                        log::error!("Error downloading modpack icon: {}", e);
+                        yield Err(anyhow::anyhow!("Error downloading modpack icon: {}", e));
+                        return;
+                    }
+
+                    let msg = format!("Downloading modpack icon: {}", config.name);
+                    yield Ok(ProgressEvent::download(Phase::DownloadIcon, res.unwrap(), msg));
+                }
+            }
         }
 
+        // Logo URLs don't always end in the extension of the image they serve
         let icon = ImageReader::open(tmp_ico.file_path())
+            .and_then(|reader| reader.with_guessed_format())
             .map_err(|e| {
                 log::error!("Failed to open icon image: {}", e);
                 anyhow::anyhow!("Failed to open icon image: {}", e)
@@ -225,23 +371,27 @@ pub fn install_modpack(
         }
 
         let icon = icon.unwrap();
+        let shortcut_icon = icon_path.with_extension("ico");
+        for path in [&icon_path, &shortcut_icon] {
+            if let Err(e) = journal.backup(path) {
+                log::error!("Failed to back up {}: {}", path.display(), e);
+                yield Err(anyhow::anyhow!("Failed to back up {}: {}", path.display(), e));
+                return;
+            }
+        }
+
         let res = icon.save(&icon_path)
             .map_err(|e| {
                 log::error!("Failed to save icon image at {}: {}", e, icon_path.display());
                 anyhow::anyhow!("Failed to save icon image: {}", e)
             });
 
-        let shortcut_icon = icon_path.with_extension("ico");
-        if !shortcut_icon.exists() {
-            if let Err(e) = icon.save(&shortcut_icon) {
-                log::error!("Failed to save icon as ICO: {}", e);
-                yield Err(anyhow::anyhow!("Failed to save icon as ICO: {}", e));
-                return;
-            }
-            log::info!("Saved icon as ICO: {:?}", shortcut_icon);
-        } else {
-            log::info!("ICO icon already exists: {:?}", shortcut_icon);
+        if let Err(e) = icon.save(&shortcut_icon) {
+            log::error!("Failed to save icon as ICO: {}", e);
+            yield Err(anyhow::anyhow!("Failed to save icon as ICO: {}", e));
+            return;
         }
+        log::info!("Saved icon as ICO: {:?}", shortcut_icon);
 
         if let Err(e) = res {
             log::error!("Failed to save icon image: {}", e);
@@ -250,7 +400,13 @@ pub fn install_modpack(
         }
 
 
-        prism_config.set("General", "iconKey", Some(icon_uuid));
+        prism_config.set("General", "iconKey", Some(icon_uuid.clone()));
+
+        if let Err(e) = journal.backup(&prism_cfg_path) {
+            log::error!("Failed to back up instance config: {}", e);
+            yield Err(anyhow::anyhow!("Failed to back up instance config: {}", e));
+            return;
+        }
 
         let write_options = WriteOptions::new_with_params(true, 2, 1);
         let save = prism_config.pretty_write(&prism_cfg_path, &write_options);
@@ -260,6 +416,31 @@ pub fn install_modpack(
             return;
         }
 
+        // Sync the mods now so the first launch only has to check them. This isn't fatal, the
+        // bootstrap jar downloads whatever is missing when the instance starts.
+        {
+            let sync = sync_mods(&mc_folder, &config.packwiz_url, &options.download, Some(&mut journal));
+            pin_mut!(sync);
+            while let Some(res) = sync.next().await {
+                match res {
+                    Ok(ModSync::Progress(event)) => yield Ok(event),
+                    Ok(ModSync::Done(report)) => {
+                        log::info!(
+                            "Synced mods: {} downloaded, {} removed, {} unchanged, {} left to the bootstrap",
+                            report.downloaded.len(),
+                            report.removed.len(),
+                            report.unchanged,
+                            report.skipped.len()
+                        );
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to sync mods, leaving it to the first launch: {:#}", e);
+                        break;
+                    }
+                }
+            }
+        }
+
         let instance_name = instance_dir.file_name()
             .and_then(|f| f.to_str().map(|s| s.to_string()))
             .ok_or_else(|| anyhow::anyhow!("Failed to get instance name from directory"));
@@ -270,18 +451,75 @@ pub fn install_modpack(
         }
 
         let instance_name = instance_name.unwrap();
-        let res = create_shortcut(&prism_exec, &instance_name, &config.name, &shortcut_icon)
-            .await
-            .map_err(|e| {
-                log::error!("Failed to create shortcut: {}", e);
-                anyhow::anyhow!("Failed to create shortcut: {}", e)
-            });
+        yield Ok(ProgressEvent::new(Phase::Finalize, 0.0, "Creating shortcuts"));
+
+        // Updates overwrite the shortcuts, which a failed update has to put back
+        let previous_manifest = previous.and_then(|p| p.manifest);
+        let mut backed_up = shortcut_paths(&options.paths, &config.name, &icon_uuid);
+        if let Some(manifest) = &previous_manifest {
+            backed_up.extend(manifest.shortcuts.iter().cloned());
+        }
+        backed_up.sort();
+        backed_up.dedup();
+        for shortcut in &backed_up {
+            if let Some(parent) = shortcut.parent() {
+                journal.track_dir(parent);
+            }
+            if let Err(e) = journal.backup(shortcut) {
+                log::error!("Failed to back up {}: {}", shortcut.display(), e);
+                yield Err(anyhow::anyhow!("Failed to back up {}: {}", shortcut.display(), e));
+                return;
+            }
+        }
+
+        let res = create_shortcut(
+            &options.paths,
+            &prism_exec,
+            &instance_name,
+            &config.name,
+            &shortcut_icon,
+        )
+        .await
+        .map_err(|e| {
+            log::error!("Failed to create shortcut: {}", e);
+            anyhow::anyhow!("Failed to create shortcut: {}", e)
+        });
 
         if let Err(e) = res {
             yield Err(e);
             return;
         }
 
+        let shortcuts = res.unwrap();
+        for shortcut in shortcuts.iter().filter(|s| !backed_up.contains(s)) {
+            journal.track_file(shortcut);
+        }
+
+        let now = instance::now();
+        let manifest = InstallManifest {
+            installer_version: env!("CARGO_PKG_VERSION").to_string(),
+            config: config.clone(),
+            installed_at: previous_manifest.map_or(now, |m| m.installed_at),
+            updated_at: now,
+            icon_key: icon_uuid,
+            shortcuts,
+            files: base_files,
+        };
+
+        let manifest = serde_json::to_vec_pretty(&manifest).expect("install manifest is always serializable");
+        if let Err(e) = journal.write(&instance_dir.join(MANIFEST_FILE), manifest).await {
+            log::error!("Failed to write install manifest: {}", e);
+            yield Err(anyhow::anyhow!("Failed to write install manifest: {}", e));
+            return;
+        }
+
+        journal.commit();
+        if !options.launch {
+            log::info!("Skipping launch of Prism Launcher");
+            yield Ok(ProgressEvent::new(Phase::Finalize, 1.0, "Done importing"));
+            return;
+        }
+
         let res = Command::new(&prism_exec)
             .arg("-l")
             .arg(&instance_name)
@@ -297,6 +535,133 @@ pub fn install_modpack(
             return;
         }
 
-        yield Ok((1.0, "Done importing".to_string()));
+        yield Ok(ProgressEvent::new(Phase::Finalize, 1.0, "Done importing"));
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::progress::{MemoryProgress, ProgressTracker, MODPACK_PHASES};
+    use crate::test_util::{self, Server};
+
+    fn logo(format: image::ImageFormat) -> Vec<u8> {
+        let mut logo = Cursor::new(Vec::new());
+        image::RgbImage::new(4, 4)
+            .write_to(&mut logo, format)
+            .unwrap();
+        logo.into_inner()
+    }
+
+    fn install_to(
+        prism_data: &Path,
+        paths: UserPaths,
+        packwiz_url: &str,
+        sink: &MemoryProgress,
+    ) -> Result<(), anyhow::Error> {
+        let options = InstallOptions {
+            launch: false,
+            paths,
+            ..Default::default()
+        };
+        tauri::async_runtime::block_on(async {
+            let mut tracker = ProgressTracker::new(sink, &MODPACK_PHASES);
+            let install = install_modpack(
+                prism_data,
+                Path::new("prismlauncher"),
+                test_util::config("Test Pack", packwiz_url),
+                options,
+            );
+            pin_mut!(install);
+            while let Some(event) = install.next().await {
+                tracker.update(event?);
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn install_reports_phases_in_order_up_to_100_percent() {
+        let tmp = test_util::temp_dir();
+        let home = tmp.file_path();
+        let server = Server::new(&[("/logo.png", &logo(image::ImageFormat::Png))]);
+        test_util::packwiz_pack(
+            &server,
+            "1.21.1",
+            &[("mods/a.jar", b"first mod"), ("mods/b.jar", b"second mod")],
+        );
+
+        let prism_data = home.join("install-progress");
+        let sink = MemoryProgress::default();
+        install_to(
+            &prism_data,
+            test_util::user_paths(home),
+            &server.url("/pack.toml"),
+            &sink,
+        )
+        .unwrap();
+
+        let events = sink.events();
+        test_util::assert_progress(&events, &MODPACK_PHASES);
+        let last = events.last().unwrap();
+        assert_eq!(last.phase, Phase::Finalize);
+        assert_eq!(last.overall, 1.0);
+        for (phase, _) in MODPACK_PHASES {
+            assert!(events.iter().any(|e| e.phase == phase), "{:?}", phase);
+        }
+
+        let mods = prism_data.join("instances/Test Pack/minecraft/mods");
+        assert_eq!(std::fs::read(mods.join("a.jar")).unwrap(), b"first mod");
+        assert_eq!(std::fs::read(mods.join("b.jar")).unwrap(), b"second mod");
+    }
+
+    #[test]
+    fn decodes_logos_by_their_content() {
+        let tmp = test_util::temp_dir();
+        let home = tmp.file_path();
+        // Served as .png, but actually a jpeg
+        let server = Server::new(&[("/logo.png", &logo(image::ImageFormat::Jpeg))]);
+        test_util::packwiz_pack(&server, "1.21.1", &[]);
+
+        let prism_data = home.join("prism");
+        install_to(
+            &prism_data,
+            test_util::user_paths(home),
+            &server.url("/pack.toml"),
+            &MemoryProgress::default(),
+        )
+        .unwrap();
+
+        let icons = std::fs::read_dir(prism_data.join("icons")).unwrap().count();
+        assert_eq!(icons, 2);
+    }
+
+    // Desktop entries are easy to write by hand, .lnk files aren't
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn failed_updates_restore_overwritten_shortcuts() {
+        let tmp = test_util::temp_dir();
+        let home = tmp.file_path();
+        let server = Server::new(&[("/logo.png", &logo(image::ImageFormat::Png))]);
+        test_util::packwiz_pack(&server, "1.21.1", &[]);
+        let prism_data = home.join("prism");
+        let url = server.url("/pack.toml");
+        let sink = MemoryProgress::default();
+        install_to(&prism_data, test_util::user_paths(home), &url, &sink).unwrap();
+
+        let desktop_entry = home.join("Desktop/Test-Pack.desktop");
+        std::fs::write(&desktop_entry, "customized").unwrap();
+        // Makes the update fail after the shortcuts were written
+        let manifest = prism_data.join("instances/Test Pack").join(MANIFEST_FILE);
+        std::fs::remove_file(&manifest).unwrap();
+        std::fs::create_dir(&manifest).unwrap();
+
+        assert!(install_to(&prism_data, test_util::user_paths(home), &url, &sink).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&desktop_entry).unwrap(),
+            "customized"
+        );
     }
 }
@@ -0,0 +1,115 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use zip::ZipArchive;
+
+use crate::github::Asset;
+
+/// Entry holding the base pack zip
+const BASE_PACK: &str = "base_pack.zip";
+/// Prefix of the logo entry, any image format `image` can guess works
+const LOGO: &str = "logo.";
+/// Directory holding PrismLauncher release assets under their original names
+const LAUNCHER_DIR: &str = "launcher/";
+
+/// Assets bundled with the installer for installs without internet
+///
+/// The payload is a zip named like the installer with `.offline.zip` appended to its stem, e.g.
+/// `Minecolonies.offline.zip` next to `Minecolonies.exe`. It may contain `base_pack.zip`, a `logo`
+/// image and PrismLauncher release assets in `launcher/`, anything missing is downloaded as usual.
+pub struct OfflinePayload {
+    path: PathBuf,
+    names: Vec<String>,
+}
+
+impl OfflinePayload {
+    /// Finds the payload next to the running installer
+    pub fn locate() -> Option<Self> {
+        let exe = std::env::current_exe().ok()?;
+        let stem = exe.file_stem()?.to_string_lossy();
+        let path = exe.with_file_name(format!("{}.offline.zip", stem));
+        if !path.is_file() {
+            return None;
+        }
+
+        match Self::open(&path) {
+            Ok(payload) => {
+                log::info!("Using offline payload {}", path.display());
+                Some(payload)
+            }
+            Err(e) => {
+                log::warn!(
+                    "Ignoring unreadable offline payload {}: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        let archive = ZipArchive::new(File::open(path)?)?;
+        let names = archive.file_names().map(str::to_string).collect();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            names,
+        })
+    }
+
+    fn extract(&self, name: &str, dest: &Path) -> Result<u64, anyhow::Error> {
+        let mut archive = ZipArchive::new(File::open(&self.path)?)?;
+        let mut entry = archive.by_name(name)?;
+        let size = std::io::copy(&mut entry, &mut File::create(dest)?)?;
+
+        log::info!("Extracted bundled {} to {}", name, dest.display());
+        Ok(size)
+    }
+
+    pub fn has_base_pack(&self) -> bool {
+        self.names.iter().any(|n| n == BASE_PACK)
+    }
+
+    fn logo(&self) -> Option<&String> {
+        self.names.iter().find(|n| n.starts_with(LOGO))
+    }
+
+    pub fn has_logo(&self) -> bool {
+        self.logo().is_some()
+    }
+
+    /// Extension of the bundled logo, e.g. `png` for `logo.png`
+    pub fn logo_extension(&self) -> Option<&str> {
+        self.logo()?.strip_prefix(LOGO)
+    }
+
+    /// Extracts the bundled base pack to `dest`, returns `None` if there is none
+    pub fn extract_base_pack(&self, dest: &Path) -> Option<Result<u64, anyhow::Error>> {
+        self.has_base_pack().then(|| self.extract(BASE_PACK, dest))
+    }
+
+    /// Extracts the bundled logo to `dest`, returns `None` if there is none
+    pub fn extract_logo(&self, dest: &Path) -> Option<Result<u64, anyhow::Error>> {
+        Some(self.extract(self.logo()?, dest))
+    }
+
+    /// Bundled PrismLauncher release assets, to pick the one for this system from
+    pub fn launcher_assets(&self) -> Vec<Asset> {
+        self.names
+            .iter()
+            .filter_map(|n| n.strip_prefix(LAUNCHER_DIR))
+            .filter(|n| !n.is_empty() && !n.ends_with('/'))
+            .map(|name| Asset {
+                name: name.to_string(),
+                browser_download_url: String::new(),
+                digest: None,
+            })
+            .collect()
+    }
+
+    /// Extracts a bundled launcher asset returned by `launcher_assets` to `dest`
+    pub fn extract_launcher(&self, asset: &Asset, dest: &Path) -> Result<u64, anyhow::Error> {
+        self.extract(&format!("{}{}", LAUNCHER_DIR, asset.name), dest)
+    }
+}
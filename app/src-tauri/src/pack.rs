@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tokio::time::timeout;
+
+use crate::download::DownloadPolicy;
+
+/// Hash formats packwiz can write into `pack.toml` and `index.toml`
+const HASH_FORMATS: [&str; 5] = ["sha256", "sha512", "sha1", "md5", "murmur2"];
+
+/// Component uid of Minecraft itself in `mmc-pack.json`
+const MINECRAFT_UID: &str = "net.minecraft";
+
+/// Mod loaders by `pack.toml` key with their name and `mmc-pack.json` component uid, in the order
+/// they're preferred when a pack somehow lists several
+const LOADERS: [(&str, &str, &str); 5] = [
+    ("neoforge", "NeoForge", "net.neoforged"),
+    ("forge", "Forge", "net.minecraftforge"),
+    ("fabric", "Fabric", "net.fabricmc.fabric-loader"),
+    ("quilt", "Quilt", "org.quiltmc.quilt-loader"),
+    ("liteloader", "LiteLoader", "com.mumfrey.liteloader"),
+];
+
+/// The `pack.toml` at the root of a packwiz modpack
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct PackToml {
+    pub name: String,
+    pub version: Option<String>,
+    /// Like `packwiz:1.1.0`
+    pub pack_format: Option<String>,
+    pub index: PackIndex,
+    pub versions: PackVersions,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct PackIndex {
+    /// Path of `index.toml`, relative to `pack.toml`
+    pub file: String,
+    pub hash_format: String,
+    pub hash: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PackVersions {
+    pub minecraft: String,
+    /// Mod loader versions keyed by loader, e.g. `fabric = "0.16.14"`
+    #[serde(flatten)]
+    pub loaders: BTreeMap<String, String>,
+}
+
+impl PackToml {
+    /// The mod loader's name and version, `None` for vanilla packs
+    pub fn loader(&self) -> Option<(&'static str, &str)> {
+        LOADERS.iter().find_map(|(id, name, _)| {
+            let version = self.versions.loaders.get(*id)?;
+            Some((*name, version.as_str()))
+        })
+    }
+
+    /// Contents of an `mmc-pack.json` with Minecraft and the mod loader in the pack's versions
+    ///
+    /// Only the top level components are listed, Prism adds their dependencies like LWJGL or
+    /// intermediary mappings itself when it loads the instance.
+    pub fn mmc_pack(&self) -> serde_json::Value {
+        let mut components = vec![serde_json::json!({
+            "uid": MINECRAFT_UID,
+            "version": self.versions.minecraft,
+            "important": true,
+        })];
+        if let Some((id, _, uid)) = LOADERS
+            .iter()
+            .find(|(id, _, _)| self.versions.loaders.contains_key(*id))
+        {
+            components.push(serde_json::json!({
+                "uid": uid,
+                "version": self.versions.loaders[*id],
+            }));
+        }
+
+        serde_json::json!({
+            "components": components,
+            "formatVersion": 1,
+        })
+    }
+}
+
+/// Location of the packwiz `pack.toml`, `packwiz_url` may point to it or to its directory
+pub fn pack_toml_url(packwiz_url: &str) -> String {
+    if packwiz_url.ends_with(".toml") {
+        packwiz_url.to_string()
+    } else {
+        format!("{}/pack.toml", packwiz_url.trim_end_matches('/'))
+    }
+}
+
+/// Parses and sanity checks the contents of a `pack.toml`
+pub fn parse(content: &str) -> Result<PackToml, anyhow::Error> {
+    let pack: PackToml = toml::from_str(content)?;
+
+    if let Some(format) = &pack.pack_format {
+        if !format.starts_with("packwiz:") {
+            anyhow::bail!("unsupported pack format '{}'", format);
+        }
+    }
+    if !HASH_FORMATS.contains(&pack.index.hash_format.as_str()) {
+        anyhow::bail!("unsupported index hash format '{}'", pack.index.hash_format);
+    }
+    if pack.index.hash.is_empty() {
+        anyhow::bail!("index hash is empty");
+    }
+    if pack.versions.minecraft.is_empty() {
+        anyhow::bail!("no Minecraft version set");
+    }
+    // Installing a pack with an unknown loader would silently leave it out of the instance
+    let unknown = pack
+        .versions
+        .loaders
+        .keys()
+        .find(|key| !LOADERS.iter().any(|(id, _, _)| id == key));
+    if let Some(loader) = unknown {
+        anyhow::bail!("unsupported mod loader '{}'", loader);
+    }
+
+    Ok(pack)
+}
+
+/// Fetches a small text file like `pack.toml`, giving up once the server stalls
+pub async fn fetch_text(
+    client: &reqwest::Client,
+    url: &str,
+    policy: &DownloadPolicy,
+) -> Result<String, anyhow::Error> {
+    let text = timeout(policy.stall_timeout, async {
+        client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+    })
+    .await
+    .with_context(|| format!("Timed out fetching {}", url))?
+    .with_context(|| format!("Failed to fetch {}", url))?;
+
+    Ok(text)
+}
+
+/// Downloads and parses the `pack.toml` of the modpack at `packwiz_url`
+pub async fn fetch(packwiz_url: &str, policy: &DownloadPolicy) -> Result<PackToml, anyhow::Error> {
+    let url = pack_toml_url(packwiz_url);
+    let client = reqwest::Client::builder()
+        .connect_timeout(policy.stall_timeout)
+        .build()?;
+
+    let content = fetch_text(&client, &url, policy).await?;
+    let pack = parse(&content).with_context(|| format!("Invalid pack.toml at {}", url))?;
+    log::info!(
+        "Found packwiz pack {} {} for Minecraft {}",
+        pack.name,
+        pack.version.as_deref().unwrap_or_default(),
+        pack.versions.minecraft
+    );
+    Ok(pack)
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ModLoader {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub version: String,
+}
+
+/// What the UI shows about the pack before installing
+#[derive(Serialize, Debug, Clone)]
+pub struct PackInfo {
+    pub pack_name: String,
+    pub version: Option<String>,
+    pub minecraft: String,
+    pub modloader: Option<ModLoader>,
+}
+
+impl From<&PackToml> for PackInfo {
+    fn from(pack: &PackToml) -> Self {
+        let modloader = pack.loader().map(|(name, version)| ModLoader {
+            kind: name.to_string(),
+            version: version.to_string(),
+        });
+
+        Self {
+            pack_name: pack.name.clone(),
+            version: pack.version.clone(),
+            minecraft: pack.versions.minecraft.clone(),
+            modloader,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = r#"
+name = "Test Pack"
+version = "1.0.0"
+pack-format = "packwiz:1.1.0"
+
+[index]
+file = "index.toml"
+hash-format = "sha256"
+hash = "0123abcd"
+
+[versions]
+minecraft = "1.21.1"
+fabric = "0.16.14"
+"#;
+
+    #[test]
+    fn parses_pack_toml() {
+        let pack = parse(PACK).unwrap();
+        assert_eq!(pack.name, "Test Pack");
+        assert_eq!(pack.version.as_deref(), Some("1.0.0"));
+        assert_eq!(pack.index.file, "index.toml");
+        assert_eq!(pack.versions.minecraft, "1.21.1");
+        assert_eq!(pack.loader(), Some(("Fabric", "0.16.14")));
+    }
+
+    #[test]
+    fn rejects_invalid_pack_toml() {
+        for (from, to, error) in [
+            ("[versions]", "[other]", "missing field `versions`"),
+            ("minecraft = \"1.21.1\"\n", "", "missing field `minecraft`"),
+            (
+                "minecraft = \"1.21.1\"",
+                "minecraft = \"\"",
+                "no Minecraft version set",
+            ),
+            ("fabric", "rift", "unsupported mod loader 'rift'"),
+            (
+                "packwiz:1.1.0",
+                "modrinth:1",
+                "unsupported pack format 'modrinth:1'",
+            ),
+            (
+                "hash-format = \"sha256\"",
+                "hash-format = \"crc32\"",
+                "unsupported index hash format 'crc32'",
+            ),
+            ("hash = \"0123abcd\"", "hash = \"\"", "index hash is empty"),
+            ("[index]", "[idx]", "missing field `index`"),
+            ("name = \"Test Pack\"", "name = 1", "invalid type"),
+        ] {
+            let content = PACK.replacen(from, to, 1);
+            assert_ne!(content, PACK, "{} isn't in the pack", from);
+            let err = format!("{:#}", parse(&content).unwrap_err());
+            assert!(err.contains(error), "{:?} for {} -> {}", err, from, to);
+        }
+    }
+
+    #[test]
+    fn vanilla_packs_have_no_loader() {
+        let pack = parse(&PACK.replace("fabric = \"0.16.14\"\n", "")).unwrap();
+        assert_eq!(pack.loader(), None);
+    }
+
+    #[test]
+    fn lists_minecraft_and_the_loader_in_mmc_pack() {
+        for (loader, uid) in [
+            ("neoforge", Some("net.neoforged")),
+            ("forge", Some("net.minecraftforge")),
+            ("fabric", Some("net.fabricmc.fabric-loader")),
+            ("quilt", Some("org.quiltmc.quilt-loader")),
+            ("liteloader", Some("com.mumfrey.liteloader")),
+            ("", None),
+        ] {
+            let content = match loader {
+                "" => PACK.replace("fabric = \"0.16.14\"\n", ""),
+                loader => PACK.replace("fabric", loader),
+            };
+            let mut expected = vec![serde_json::json!({
+                "uid": "net.minecraft",
+                "version": "1.21.1",
+                "important": true,
+            })];
+            if let Some(uid) = uid {
+                expected.push(serde_json::json!({ "uid": uid, "version": "0.16.14" }));
+            }
+
+            let mmc_pack = parse(&content).unwrap().mmc_pack();
+            assert_eq!(mmc_pack["formatVersion"], 1);
+            assert_eq!(
+                mmc_pack["components"],
+                serde_json::Value::Array(expected),
+                "{}",
+                loader
+            );
+        }
+    }
+
+    #[test]
+    fn prefers_neoforge_over_other_loaders() {
+        let content = PACK.replace("fabric = ", "neoforge = \"21.1.1\"\nfabric = ");
+        let pack = parse(&content).unwrap();
+        assert_eq!(pack.loader(), Some(("NeoForge", "21.1.1")));
+        assert_eq!(pack.mmc_pack()["components"][1]["uid"], "net.neoforged");
+        assert_eq!(pack.mmc_pack()["components"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn finds_pack_toml() {
+        for (url, expected) in [
+            (
+                "https://example.com/pack/pack.toml",
+                "https://example.com/pack/pack.toml",
+            ),
+            (
+                "https://example.com/pack/other.toml",
+                "https://example.com/pack/other.toml",
+            ),
+            (
+                "https://example.com/pack",
+                "https://example.com/pack/pack.toml",
+            ),
+            (
+                "https://example.com/pack/",
+                "https://example.com/pack/pack.toml",
+            ),
+        ] {
+            assert_eq!(pack_toml_url(url), expected);
+        }
+    }
+}
@@ -0,0 +1,931 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Context;
+use async_stream::try_stream;
+use futures_core::Stream;
+use futures_util::{pin_mut, StreamExt};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use url::Url;
+
+use crate::download::{download, DownloadPolicy};
+use crate::journal::InstallJournal;
+use crate::pack::{self, pack_toml_url};
+use crate::progress::{Phase, ProgressEvent};
+
+/// packwiz-installer's record of what it installed, which `packwiz_bootstrap.jar` reads on launch
+///
+/// The native sync keeps it up to date as well, so whichever runs next knows what the other wrote.
+const PACKWIZ_MANIFEST: &str = "packwiz.json";
+/// Where native syncs used to remember their files, migrated into `packwiz.json` on the next sync
+const LEGACY_STATE_FILE: &str = ".packwiz-sync.json";
+
+/// The `index.toml` listing every file of a packwiz modpack
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct Index {
+    hash_format: String,
+    #[serde(default)]
+    files: Vec<IndexFile>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct IndexFile {
+    /// Path relative to `index.toml`
+    file: String,
+    hash: String,
+    /// Overrides the index' hash format for this file
+    hash_format: Option<String>,
+    /// Where to put the file instead of `file`, or instead of the metafile's `filename`
+    alias: Option<String>,
+    /// A `.pw.toml` describing a file to download from elsewhere
+    #[serde(default)]
+    metafile: bool,
+    /// Only written if it doesn't exist yet, so players can change it
+    #[serde(default)]
+    preserve: bool,
+}
+
+/// A `.pw.toml` metafile, usually describing a mod
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct Metafile {
+    name: String,
+    filename: String,
+    side: Option<String>,
+    download: MetafileDownload,
+    option: Option<MetafileOption>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct MetafileDownload {
+    url: Option<String>,
+    hash_format: String,
+    hash: String,
+    /// `metadata:curseforge` for files that have to be looked up through the CurseForge API
+    mode: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetafileOption {
+    #[serde(default)]
+    optional: bool,
+    #[serde(default)]
+    default: bool,
+}
+
+/// A hash the way packwiz-installer stores it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Hash {
+    #[serde(rename = "type")]
+    format: String,
+    value: String,
+}
+
+impl Hash {
+    fn new(format: &str, value: &str) -> Self {
+        Self {
+            format: format.to_string(),
+            value: value.trim().to_lowercase(),
+        }
+    }
+}
+
+/// packwiz-installer's `packwiz.json`
+///
+/// Fields this doesn't know about are written back untouched.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    /// sha256 of `pack.toml`, the bootstrap skips the update entirely while it matches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pack_file_hash: Option<Hash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index_file_hash: Option<Hash>,
+    /// Keyed by the entry's path in the index
+    #[serde(default)]
+    cached_files: BTreeMap<String, CachedFile>,
+    #[serde(default = "client_side")]
+    cached_side: String,
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct CachedFile {
+    /// Hash of the index entry, which is the metafile's for mods
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash: Option<Hash>,
+    /// Hash of the file a metafile downloads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    linked_file_hash: Option<Hash>,
+    /// Where the file was written, relative to the minecraft directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cached_location: Option<String>,
+    // packwiz-installer leaves out flags that are false
+    #[serde(default, skip_serializing_if = "is_false")]
+    is_optional: bool,
+    #[serde(default = "enabled")]
+    option_value: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    only_other_side: bool,
+}
+
+fn client_side() -> String {
+    "client".to_string()
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            pack_file_hash: None,
+            index_file_hash: None,
+            cached_files: BTreeMap::new(),
+            cached_side: client_side(),
+            other: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Manifest {
+    fn read(minecraft_dir: &Path) -> Self {
+        std::fs::read(minecraft_dir.join(PACKWIZ_MANIFEST))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Files written by an earlier sync, relative to the minecraft directory
+    fn locations(&self) -> impl Iterator<Item = &String> {
+        self.cached_files
+            .values()
+            .filter_map(|f| f.cached_location.as_ref())
+    }
+}
+
+/// Files the native sync wrote before it shared `packwiz.json` with the bootstrap
+fn read_legacy_state(minecraft_dir: &Path) -> Vec<String> {
+    #[derive(Deserialize)]
+    struct LegacyState {
+        files: BTreeMap<String, String>,
+    }
+
+    std::fs::read(minecraft_dir.join(LEGACY_STATE_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_slice::<LegacyState>(&content).ok())
+        .map(|state| state.files.into_keys().collect())
+        .unwrap_or_default()
+}
+
+/// A file the pack wants at `dest`
+struct Target {
+    dest: String,
+    /// `None` for files that have to be looked up through the CurseForge API
+    url: Option<Url>,
+    hash_format: String,
+    hash: String,
+    preserve: bool,
+    optional: bool,
+}
+
+/// What an index entry means for a client install
+enum Resolved {
+    File(Target),
+    /// Only used on servers
+    OtherSide,
+    /// An optional file that isn't enabled
+    Disabled,
+}
+
+/// What a sync changed in the minecraft directory
+#[derive(Serialize, Debug, Default)]
+pub struct SyncReport {
+    pub downloaded: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+    /// Files the native sync can't handle, left to the packwiz bootstrap
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum SyncEvent {
+    /// Started working on the `current` of `total` files
+    File {
+        current: usize,
+        total: usize,
+        path: String,
+    },
+    /// About to create, replace or remove `path`, sent before it's touched
+    Write(PathBuf),
+    Done(SyncReport),
+}
+
+/// The CurseForge fingerprint, murmur2 with seed 1 over the file without whitespace
+fn murmur2(data: &[u8]) -> u32 {
+    const M: u32 = 0x5bd1e995;
+    let data = data
+        .iter()
+        .copied()
+        .filter(|b| !matches!(b, 9 | 10 | 13 | 32))
+        .collect::<Vec<_>>();
+
+    let mut h = 1 ^ data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M) ^ k;
+    }
+
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        for (i, b) in rest.iter().enumerate() {
+            h ^= (*b as u32) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^ (h >> 15)
+}
+
+/// Hashes `data` the way packwiz writes hashes of `format`
+fn hash_bytes(format: &str, data: &[u8]) -> Result<String, anyhow::Error> {
+    let hash = match format {
+        "sha256" => hex::encode(Sha256::digest(data)),
+        "sha512" => hex::encode(Sha512::digest(data)),
+        "sha1" => hex::encode(Sha1::digest(data)),
+        "md5" => hex::encode(Md5::digest(data)),
+        "murmur2" => murmur2(data).to_string(),
+        other => anyhow::bail!("unsupported hash format '{}'", other),
+    };
+
+    Ok(hash)
+}
+
+fn hash_matches(format: &str, data: &[u8], expected: &str) -> Result<bool, anyhow::Error> {
+    Ok(hash_bytes(format, data)?.eq_ignore_ascii_case(expected.trim()))
+}
+
+/// Rejects paths that would end up outside the minecraft directory
+fn safe_path(path: &str) -> Result<String, anyhow::Error> {
+    let normal = Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !normal || path.is_empty() {
+        anyhow::bail!("refusing to write outside the instance: {}", path);
+    }
+
+    Ok(path.trim_start_matches("./").to_string())
+}
+
+/// Fetches a metafile or plain index entry and turns it into what has to end up on disk
+///
+/// Optional files keep the choice in `option_value`, which the bootstrap stores when the player
+/// picks them, and fall back to the metafile's default.
+async fn resolve(
+    client: &reqwest::Client,
+    index_url: &Url,
+    index_format: &str,
+    entry: &IndexFile,
+    option_value: Option<bool>,
+    policy: &DownloadPolicy,
+) -> Result<Resolved, anyhow::Error> {
+    let url = index_url.join(&entry.file)?;
+    let hash_format = entry.hash_format.as_deref().unwrap_or(index_format);
+    if !entry.metafile {
+        return Ok(Resolved::File(Target {
+            dest: safe_path(entry.alias.as_deref().unwrap_or(&entry.file))?,
+            url: Some(url),
+            hash_format: hash_format.to_string(),
+            hash: entry.hash.clone(),
+            preserve: entry.preserve,
+            optional: false,
+        }));
+    }
+
+    let content = pack::fetch_text(client, url.as_str(), policy).await?;
+    if !hash_matches(hash_format, content.as_bytes(), &entry.hash)? {
+        anyhow::bail!("{} doesn't match the hash in the index", entry.file);
+    }
+    let meta: Metafile =
+        toml::from_str(&content).with_context(|| format!("Invalid metafile {}", entry.file))?;
+
+    if meta.side.as_deref() == Some("server") {
+        log::debug!("Skipping server side {}", meta.name);
+        return Ok(Resolved::OtherSide);
+    }
+    let optional = meta.option.as_ref().is_some_and(|o| o.optional);
+    if optional && !option_value.unwrap_or(meta.option.as_ref().is_some_and(|o| o.default)) {
+        log::info!("Skipping optional {}, it's disabled", meta.name);
+        return Ok(Resolved::Disabled);
+    }
+
+    let download_url = match (&meta.download.url, &meta.download.mode) {
+        (Some(url), None) => Some(Url::parse(url)?),
+        (Some(url), Some(mode)) if mode == "url" => Some(Url::parse(url)?),
+        _ => None,
+    };
+
+    // Like packwiz-installer, an alias replaces the metafile's folder and file name entirely
+    let dir = Path::new(&entry.file)
+        .parent()
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+    let dest = match (&entry.alias, dir.is_empty()) {
+        (Some(alias), _) => alias.clone(),
+        (None, true) => meta.filename.clone(),
+        (None, false) => format!("{}/{}", dir, meta.filename),
+    };
+
+    Ok(Resolved::File(Target {
+        dest: safe_path(&dest)?,
+        url: download_url,
+        hash_format: meta.download.hash_format,
+        hash: meta.download.hash,
+        preserve: entry.preserve,
+        optional,
+    }))
+}
+
+/// Where a file is downloaded to before it's moved to `dest`
+fn part_path(dest: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.part", dest.display()))
+}
+
+/// Downloads `target` next to its destination and moves it into place once its hash checks out
+async fn fetch_target(
+    minecraft_dir: &Path,
+    target: &Target,
+    policy: &DownloadPolicy,
+) -> Result<(), anyhow::Error> {
+    let dest = minecraft_dir.join(&target.dest);
+    if let Some(parent) = dest.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let part = part_path(&dest);
+    let res = async {
+        let url = target.url.as_ref().context("no download url")?;
+        let download = download(url.as_str(), &part, policy);
+        pin_mut!(download);
+        while let Some(progress) = download.next().await {
+            progress?;
+        }
+
+        let content = tokio::fs::read(&part).await?;
+        if !hash_matches(&target.hash_format, &content, &target.hash)? {
+            anyhow::bail!("Checksum mismatch for {}", target.dest);
+        }
+
+        tokio::fs::rename(&part, &dest).await?;
+        Ok(())
+    }
+    .await;
+
+    if res.is_err() {
+        let _ = tokio::fs::remove_file(&part).await;
+    }
+    res
+}
+
+/// Brings `minecraft_dir` in line with the packwiz modpack at `packwiz_url`
+///
+/// Does what `packwiz_bootstrap.jar` does on launch for client installs: downloads new and changed
+/// files, verifying their hashes, and removes files an earlier sync wrote that the pack dropped.
+/// Both record what they wrote in packwiz-installer's `packwiz.json`, so each one cleans up after
+/// the other. Files the player added are never touched. Mods that need the CurseForge API are
+/// skipped and left to the bootstrap. Every path is announced with [`SyncEvent::Write`] before
+/// it's touched, so callers can back it up.
+pub fn sync(
+    minecraft_dir: &Path,
+    packwiz_url: &str,
+    policy: &DownloadPolicy,
+) -> impl Stream<Item = Result<SyncEvent, anyhow::Error>> {
+    let minecraft_dir = minecraft_dir.to_owned();
+    let packwiz_url = packwiz_url.to_owned();
+    let policy = *policy;
+    try_stream! {
+        let client = reqwest::Client::builder()
+            .connect_timeout(policy.stall_timeout)
+            .build()?;
+
+        let pack_url = Url::parse(&pack_toml_url(&packwiz_url))?;
+        let pack_content = pack::fetch_text(&client, pack_url.as_str(), &policy).await?;
+        let pack = pack::parse(&pack_content)
+            .with_context(|| format!("Invalid pack.toml at {}", pack_url))?;
+
+        let index_url = pack_url.join(&pack.index.file)?;
+        let index_content = pack::fetch_text(&client, index_url.as_str(), &policy).await?;
+        if !hash_matches(&pack.index.hash_format, index_content.as_bytes(), &pack.index.hash)? {
+            Err(anyhow::anyhow!("{} doesn't match the hash in pack.toml", index_url))?;
+        }
+        let index: Index = toml::from_str(&index_content)
+            .with_context(|| format!("Invalid index at {}", index_url))?;
+
+        let index_hash = Hash::new(&pack.index.hash_format, &pack.index.hash);
+        let previous = Manifest::read(&minecraft_dir);
+        let legacy = read_legacy_state(&minecraft_dir);
+        let up_to_date = previous.index_file_hash.as_ref() == Some(&index_hash)
+            && previous.cached_side == client_side()
+            && legacy.is_empty()
+            && previous.locations().all(|f| minecraft_dir.join(f).is_file());
+        if up_to_date {
+            log::info!("{} is up to date", minecraft_dir.display());
+            yield SyncEvent::Done(SyncReport { unchanged: previous.locations().count(), ..SyncReport::default() });
+            return;
+        }
+
+        let mut cached_files = BTreeMap::new();
+        let mut report = SyncReport::default();
+
+        let total = index.files.len();
+        for (current, entry) in index.files.iter().enumerate() {
+            yield SyncEvent::File { current, total, path: entry.file.clone() };
+
+            let cached = previous.cached_files.get(&entry.file);
+            let option_value = cached.filter(|f| f.is_optional).map(|f| f.option_value);
+            let resolved = resolve(
+                &client,
+                &index_url,
+                &index.hash_format,
+                entry,
+                option_value,
+                &policy,
+            )
+            .await
+            .with_context(|| format!("Failed to resolve {}", entry.file))?;
+
+            let hash = Some(Hash::new(
+                entry.hash_format.as_deref().unwrap_or(&index.hash_format),
+                &entry.hash,
+            ));
+            let target = match resolved {
+                Resolved::File(target) => target,
+                Resolved::OtherSide => {
+                    cached_files.insert(entry.file.clone(), CachedFile {
+                        hash,
+                        linked_file_hash: None,
+                        cached_location: None,
+                        is_optional: false,
+                        option_value: true,
+                        only_other_side: true,
+                    });
+                    continue;
+                }
+                Resolved::Disabled => {
+                    cached_files.insert(entry.file.clone(), CachedFile {
+                        hash,
+                        linked_file_hash: None,
+                        cached_location: None,
+                        is_optional: true,
+                        option_value: false,
+                        only_other_side: false,
+                    });
+                    continue;
+                }
+            };
+
+            let dest = minecraft_dir.join(&target.dest);
+            let unchanged = match tokio::fs::read(&dest).await {
+                Ok(_) if target.preserve => true,
+                Ok(content) => hash_matches(&target.hash_format, &content, &target.hash)?,
+                Err(_) => false,
+            };
+
+            if target.url.is_none() && !unchanged {
+                // Without an entry in packwiz.json the bootstrap downloads it on the next launch
+                log::warn!("Leaving {} to the packwiz bootstrap, it needs the CurseForge API", target.dest);
+                report.skipped.push(target.dest);
+                continue;
+            } else if unchanged {
+                report.unchanged += 1;
+            } else {
+                yield SyncEvent::Write(part_path(&dest));
+                yield SyncEvent::Write(dest);
+                fetch_target(&minecraft_dir, &target, &policy)
+                    .await
+                    .with_context(|| format!("Failed to download {}", target.dest))?;
+                log::info!("Synced {}", target.dest);
+                report.downloaded.push(target.dest.clone());
+            }
+
+            let linked_file_hash = entry
+                .metafile
+                .then(|| Hash::new(&target.hash_format, &target.hash));
+            cached_files.insert(entry.file.clone(), CachedFile {
+                hash,
+                linked_file_hash,
+                cached_location: Some(target.dest),
+                is_optional: target.optional,
+                option_value: true,
+                only_other_side: false,
+            });
+        }
+
+        // Files left to the bootstrap are still part of the pack, even if they don't exist yet
+        let mut kept = cached_files
+            .values()
+            .filter_map(|f| f.cached_location.clone())
+            .collect::<BTreeSet<_>>();
+        kept.extend(report.skipped.iter().cloned());
+        let written = previous.locations().chain(&legacy).collect::<BTreeSet<_>>();
+        for file in written.into_iter().filter(|f| !kept.contains(*f)) {
+            let Ok(file) = safe_path(file) else {
+                continue;
+            };
+            let path = minecraft_dir.join(&file);
+            yield SyncEvent::Write(path.clone());
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {
+                    log::info!("Removed {}, it's no longer part of the pack", file);
+                    report.removed.push(file);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => log::warn!("Failed to remove {}: {}", file, e),
+            }
+        }
+
+        // Matching hashes make the bootstrap skip the update, so they're left out while files
+        // still have to be downloaded by it
+        let complete = report.skipped.is_empty();
+        let pack_hash = Hash::new("sha256", &hash_bytes("sha256", pack_content.as_bytes())?);
+        let manifest = Manifest {
+            pack_file_hash: complete.then_some(pack_hash),
+            index_file_hash: complete.then_some(index_hash),
+            cached_files,
+            cached_side: client_side(),
+            other: previous.other,
+        };
+        let manifest_file = minecraft_dir.join(PACKWIZ_MANIFEST);
+        yield SyncEvent::Write(manifest_file.clone());
+        tokio::fs::write(manifest_file, serde_json::to_vec_pretty(&manifest)?).await?;
+
+        let legacy_file = minecraft_dir.join(LEGACY_STATE_FILE);
+        if legacy_file.exists() {
+            yield SyncEvent::Write(legacy_file.clone());
+            tokio::fs::remove_file(legacy_file).await?;
+        }
+
+        log::info!(
+            "Synced {}: {} downloaded, {} removed, {} unchanged, {} skipped",
+            minecraft_dir.display(),
+            report.downloaded.len(),
+            report.removed.len(),
+            report.unchanged,
+            report.skipped.len()
+        );
+        yield SyncEvent::Done(report);
+    }
+}
+
+/// What [`sync_mods`] reports while it runs
+pub enum ModSync {
+    Progress(ProgressEvent),
+    Done(SyncReport),
+}
+
+/// Drives a packwiz sync, reporting every file as progress of the `SyncMods` phase
+///
+/// With a `journal`, every file the sync writes or removes is recorded so it can be rolled back.
+pub fn sync_mods<'a>(
+    minecraft_dir: &Path,
+    packwiz_url: &str,
+    policy: &DownloadPolicy,
+    mut journal: Option<&'a mut InstallJournal>,
+) -> impl Stream<Item = Result<ModSync, anyhow::Error>> + 'a {
+    let sync = sync(minecraft_dir, packwiz_url, policy);
+    try_stream! {
+        pin_mut!(sync);
+        yield ModSync::Progress(ProgressEvent::new(Phase::SyncMods, 0.0, "Syncing mods"));
+
+        let mut report = None;
+        while let Some(event) = sync.next().await {
+            match event? {
+                SyncEvent::File { current, total, path } => {
+                    yield ModSync::Progress(ProgressEvent::new(
+                        Phase::SyncMods,
+                        current as f32 / total.max(1) as f32,
+                        format!("Syncing {} ({}/{})", path, current + 1, total),
+                    ));
+                }
+                SyncEvent::Write(path) => {
+                    if let Some(journal) = journal.as_deref_mut() {
+                        if let Some(parent) = path.parent() {
+                            journal.track_dir(parent);
+                        }
+                        journal
+                            .backup(&path)
+                            .with_context(|| format!("Failed to back up {}", path.display()))?;
+                    }
+                }
+                SyncEvent::Done(done) => report = Some(done),
+            }
+        }
+
+        let report = report.context("Mod sync ended without a result")?;
+        yield ModSync::Progress(ProgressEvent::new(Phase::SyncMods, 1.0, "Mods are up to date"));
+        yield ModSync::Done(report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, Server};
+
+    fn sync_to(minecraft_dir: &Path, packwiz_url: &str) -> Result<SyncReport, anyhow::Error> {
+        tauri::async_runtime::block_on(async {
+            let sync = sync(minecraft_dir, packwiz_url, &DownloadPolicy::default());
+            pin_mut!(sync);
+            let mut report = None;
+            while let Some(event) = sync.next().await {
+                if let SyncEvent::Done(done) = event? {
+                    report = Some(done);
+                }
+            }
+            Ok(report.unwrap())
+        })
+    }
+
+    fn sync_mods_to(minecraft_dir: &Path, packwiz_url: &str, journal: Option<&mut InstallJournal>) {
+        tauri::async_runtime::block_on(async {
+            let sync = sync_mods(
+                minecraft_dir,
+                packwiz_url,
+                &DownloadPolicy::default(),
+                journal,
+            );
+            pin_mut!(sync);
+            while let Some(event) = sync.next().await {
+                event.unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn murmur2_matches_the_reference_implementation() {
+        // Values of the original MurmurHash2 with seed 1, covering every remainder length
+        for (data, expected) in [
+            ("", 1540447798),
+            ("a", 626045324),
+            ("ab", 1692487918),
+            ("abc", 1621425345),
+            ("abcd", 3376380438),
+            ("abcde", 3469237630),
+            ("{\"modid\":\"test\"}", 2775448841),
+        ] {
+            assert_eq!(murmur2(data.as_bytes()), expected, "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn murmur2_ignores_whitespace_like_curseforge() {
+        let plain = murmur2(b"Thequickbrownfoxjumpsoverthelazydog");
+        assert_eq!(
+            murmur2(b"The quick brown fox jumps over the lazy dog"),
+            plain
+        );
+        assert_eq!(
+            murmur2(b"The\tquick\r\nbrown fox\njumps over the lazy dog "),
+            plain
+        );
+        // Only tab, newline, carriage return and space count as whitespace
+        assert_ne!(murmur2(b"The\x0bquickbrownfoxjumpsoverthelazydog"), plain);
+    }
+
+    #[test]
+    fn hashes_in_every_packwiz_format() {
+        for (format, expected) in [
+            ("sha256", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            ("sha512", "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"),
+            ("sha1", "a9993e364706816aba3e25717850c26c9cd0d89d"),
+            ("md5", "900150983cd24fb0d6963f7d28e17f72"),
+            ("murmur2", "1621425345"),
+        ] {
+            assert_eq!(hash_bytes(format, b"abc").unwrap(), expected, "{}", format);
+        }
+        assert!(hash_bytes("crc32", b"abc").is_err());
+        assert!(hash_matches("md5", b"abc", " 900150983CD24FB0D6963F7D28E17F72\n").unwrap());
+        assert!(!hash_matches("md5", b"abd", "900150983cd24fb0d6963f7d28e17f72").unwrap());
+    }
+
+    #[test]
+    fn keeps_paths_inside_the_instance() {
+        assert_eq!(safe_path("mods/a.jar").unwrap(), "mods/a.jar");
+        assert_eq!(safe_path("./mods/a.jar").unwrap(), "mods/a.jar");
+        assert_eq!(safe_path("options.txt").unwrap(), "options.txt");
+        for path in [
+            "",
+            "..",
+            "../options.txt",
+            "mods/../../options.txt",
+            "mods/../a.jar",
+            "/etc/passwd",
+        ] {
+            assert!(safe_path(path).is_err(), "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn metafile_aliases_replace_the_destination() {
+        let server = Server::new(&[]);
+        test_util::packwiz_pack(&server, "1.21.1", &[("mods/a.jar", b"a")]);
+        let index_url = Url::parse(&server.url("/index.toml")).unwrap();
+        let resolve = |alias: &str| {
+            tauri::async_runtime::block_on(async {
+                let client = reqwest::Client::new();
+                let policy = DownloadPolicy::default();
+                let metafile = pack::fetch_text(&client, &server.url("/mods/a.pw.toml"), &policy)
+                    .await
+                    .unwrap();
+                let entry = IndexFile {
+                    file: "mods/a.pw.toml".to_string(),
+                    hash: hash_bytes("sha256", metafile.as_bytes()).unwrap(),
+                    hash_format: None,
+                    alias: Some(alias.to_string()),
+                    metafile: true,
+                    preserve: false,
+                };
+                resolve(&client, &index_url, "sha256", &entry, None, &policy).await
+            })
+        };
+        let Resolved::File(target) = resolve("mods/renamed.jar").unwrap() else {
+            panic!("mods/a.pw.toml didn't resolve to a file");
+        };
+        assert_eq!(target.dest, "mods/renamed.jar");
+        assert!(resolve("../renamed.jar").is_err());
+    }
+
+    #[test]
+    fn syncs_changes_and_keeps_player_files() {
+        let server = Server::new(&[]);
+        let tmp = test_util::temp_dir();
+        let minecraft = tmp.file_path();
+        std::fs::create_dir_all(minecraft.join("mods")).unwrap();
+        std::fs::write(minecraft.join("mods/own.jar"), b"own").unwrap();
+
+        test_util::packwiz_pack(
+            &server,
+            "1.21.1",
+            &[("mods/a.jar", b"a"), ("mods/b.jar", b"b")],
+        );
+        let report = sync_to(minecraft, &server.url).unwrap();
+        assert_eq!(report.downloaded, ["mods/a.jar", "mods/b.jar"]);
+        assert_eq!(std::fs::read(minecraft.join("mods/b.jar")).unwrap(), b"b");
+
+        let report = sync_to(minecraft, &server.url).unwrap();
+        assert!(report.downloaded.is_empty());
+        assert_eq!(report.unchanged, 2);
+
+        test_util::packwiz_pack(&server, "1.21.1", &[("mods/a.jar", b"a2")]);
+        let report = sync_to(minecraft, &server.url).unwrap();
+        assert_eq!(report.downloaded, ["mods/a.jar"]);
+        assert_eq!(report.removed, ["mods/b.jar"]);
+        assert_eq!(std::fs::read(minecraft.join("mods/a.jar")).unwrap(), b"a2");
+        assert!(!minecraft.join("mods/b.jar").exists());
+        assert_eq!(
+            std::fs::read(minecraft.join("mods/own.jar")).unwrap(),
+            b"own"
+        );
+    }
+
+    #[test]
+    fn rolls_back_journaled_syncs() {
+        let server = Server::new(&[]);
+        let tmp = test_util::temp_dir();
+        let instance = tmp.file_path().join("Pack");
+        let minecraft = instance.join("minecraft");
+        std::fs::create_dir_all(&minecraft).unwrap();
+
+        test_util::packwiz_pack(
+            &server,
+            "1.21.1",
+            &[("mods/a.jar", b"a"), ("mods/b.jar", b"b")],
+        );
+        sync_mods_to(&minecraft, &server.url, None);
+        let state = std::fs::read(minecraft.join(PACKWIZ_MANIFEST)).unwrap();
+
+        test_util::packwiz_pack(
+            &server,
+            "1.21.1",
+            &[("mods/a.jar", b"a2"), ("config/extra/c.jar", b"c")],
+        );
+        let mut journal = InstallJournal::new(&instance);
+        sync_mods_to(&minecraft, &server.url, Some(&mut journal));
+        assert_eq!(std::fs::read(minecraft.join("mods/a.jar")).unwrap(), b"a2");
+        assert!(!minecraft.join("mods/b.jar").exists());
+        assert!(minecraft.join("config/extra/c.jar").exists());
+
+        drop(journal);
+        assert_eq!(std::fs::read(minecraft.join("mods/a.jar")).unwrap(), b"a");
+        assert_eq!(std::fs::read(minecraft.join("mods/b.jar")).unwrap(), b"b");
+        assert!(!minecraft.join("config").exists());
+        assert_eq!(
+            std::fs::read(minecraft.join(PACKWIZ_MANIFEST)).unwrap(),
+            state
+        );
+    }
+
+    #[test]
+    fn dropped_mods_are_removed_on_the_next_sync() {
+        let server = Server::new(&[]);
+        let tmp = test_util::temp_dir();
+        let minecraft = tmp.file_path();
+
+        test_util::packwiz_pack(
+            &server,
+            "1.21.1",
+            &[("mods/a.jar", b"a"), ("mods/b.jar", b"b")],
+        );
+        sync_mods_to(minecraft, &server.url, None);
+
+        // Written in packwiz-installer's format, so the bootstrap knows about b.jar on launch
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(minecraft.join(PACKWIZ_MANIFEST)).unwrap())
+                .unwrap();
+        let b = &manifest["cachedFiles"]["mods/b.pw.toml"];
+        assert_eq!(b["cachedLocation"], "mods/b.jar");
+        assert_eq!(b["hash"]["type"], "sha256");
+        assert_eq!(
+            b["linkedFileHash"]["value"],
+            hex::encode(Sha256::digest(b"b"))
+        );
+        assert_eq!(manifest["packFileHash"]["type"], "sha256");
+        assert_eq!(manifest["cachedSide"], "client");
+
+        test_util::packwiz_pack(&server, "1.21.1", &[("mods/a.jar", b"a")]);
+        sync_mods_to(minecraft, &server.url, None);
+        assert!(minecraft.join("mods/a.jar").exists());
+        assert!(!minecraft.join("mods/b.jar").exists());
+        let manifest = Manifest::read(minecraft);
+        assert_eq!(
+            manifest.cached_files.keys().collect::<Vec<_>>(),
+            ["mods/a.pw.toml"]
+        );
+    }
+
+    #[test]
+    fn removes_files_the_bootstrap_installed() {
+        let server = Server::new(&[]);
+        let tmp = test_util::temp_dir();
+        let minecraft = tmp.file_path();
+        std::fs::create_dir_all(minecraft.join("mods")).unwrap();
+        std::fs::write(minecraft.join("mods/old.jar"), b"old").unwrap();
+        let manifest = r#"{
+            "packFileHash": {"type": "sha256", "value": "00"},
+            "indexFileHash": {"type": "sha256", "value": "00"},
+            "cachedFiles": {
+                "mods/old.pw.toml": {
+                    "hash": {"type": "sha256", "value": "00"},
+                    "linkedFileHash": {"type": "sha1", "value": "00"},
+                    "cachedLocation": "mods/old.jar",
+                    "optionValue": true
+                }
+            },
+            "cachedSide": "client",
+            "futureField": 1
+        }"#;
+        std::fs::write(minecraft.join(PACKWIZ_MANIFEST), manifest).unwrap();
+
+        test_util::packwiz_pack(&server, "1.21.1", &[("mods/a.jar", b"a")]);
+        let report = sync_to(minecraft, &server.url).unwrap();
+        assert_eq!(report.removed, ["mods/old.jar"]);
+        assert!(!minecraft.join("mods/old.jar").exists());
+        assert_eq!(Manifest::read(minecraft).other["futureField"], 1);
+    }
+
+    #[test]
+    fn migrates_files_of_the_legacy_state() {
+        let server = Server::new(&[]);
+        let tmp = test_util::temp_dir();
+        let minecraft = tmp.file_path();
+        std::fs::create_dir_all(minecraft.join("mods")).unwrap();
+        std::fs::write(minecraft.join("mods/old.jar"), b"old").unwrap();
+        let state = r#"{"index_hash": "00", "files": {"mods/old.jar": "sha256:00"}}"#;
+        std::fs::write(minecraft.join(LEGACY_STATE_FILE), state).unwrap();
+
+        test_util::packwiz_pack(&server, "1.21.1", &[("mods/a.jar", b"a")]);
+        let report = sync_to(minecraft, &server.url).unwrap();
+        assert_eq!(report.removed, ["mods/old.jar"]);
+        assert!(!minecraft.join(LEGACY_STATE_FILE).exists());
+        assert!(minecraft.join(PACKWIZ_MANIFEST).exists());
+    }
+}
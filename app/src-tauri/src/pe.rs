@@ -0,0 +1,240 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// Index of `IMAGE_DIRECTORY_ENTRY_SECURITY` in the optional header's data directories
+const SECURITY_DIRECTORY: u32 = 4;
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+/// `dwLength`, `wRevision` and `wCertificateType` of a `WIN_CERTIFICATE`
+const WIN_CERTIFICATE_HEADER: u64 = 8;
+
+/// Location of the Authenticode certificate table of a PE image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CertificateTable {
+    /// File offset of the first `WIN_CERTIFICATE` entry
+    pub offset: u64,
+    pub size: u64,
+    /// File offset of the security data directory entry pointing at the table
+    pub directory_entry: u64,
+}
+
+impl CertificateTable {
+    pub fn end(&self) -> u64 {
+        self.offset + self.size
+    }
+}
+
+fn read_u16<R: Read + Seek>(reader: &mut R, offset: u64) -> std::io::Result<u16> {
+    let mut buf = [0u8; 2];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read + Seek>(reader: &mut R, offset: u64) -> std::io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn write_u32<W: Write + Seek>(writer: &mut W, offset: u64, value: u32) -> std::io::Result<()> {
+    writer.seek(SeekFrom::Start(offset))?;
+    writer.write_all(&value.to_le_bytes())
+}
+
+/// Finds the certificate table of a PE image, returns `None` for unsigned or non-PE files
+pub fn certificate_table<R: Read + Seek>(
+    reader: &mut R,
+) -> std::io::Result<Option<CertificateTable>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if len < 0x40 || read_u16(reader, 0)? != u16::from_le_bytes(*b"MZ") {
+        return Ok(None);
+    }
+
+    let pe_header = read_u32(reader, 0x3c)? as u64;
+    if pe_header + 24 > len || read_u32(reader, pe_header)? != u32::from_le_bytes(*b"PE\0\0") {
+        return Ok(None);
+    }
+
+    // The optional header follows the 4 byte signature and the 20 byte COFF header
+    let optional_header = pe_header + 24;
+    let (rva_count_offset, directories_offset) = match read_u16(reader, optional_header)? {
+        PE32_MAGIC => (92, 96),
+        PE32_PLUS_MAGIC => (108, 112),
+        _ => return Ok(None),
+    };
+
+    if optional_header + directories_offset > len
+        || read_u32(reader, optional_header + rva_count_offset)? <= SECURITY_DIRECTORY
+    {
+        return Ok(None);
+    }
+
+    // Unlike other data directories, the security directory holds a file offset instead of an RVA
+    let directory_entry = optional_header + directories_offset + SECURITY_DIRECTORY as u64 * 8;
+    let offset = read_u32(reader, directory_entry)? as u64;
+    let size = read_u32(reader, directory_entry + 4)? as u64;
+    if offset == 0 || size == 0 || offset + size > len {
+        return Ok(None);
+    }
+
+    Ok(Some(CertificateTable {
+        offset,
+        size,
+        directory_entry,
+    }))
+}
+
+fn invalid(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Reads the length of the first `WIN_CERTIFICATE` entry, including its header
+fn first_entry_length<R: Read + Seek>(
+    reader: &mut R,
+    table: &CertificateTable,
+) -> std::io::Result<u64> {
+    let length = read_u32(reader, table.offset)? as u64;
+    if length < WIN_CERTIFICATE_HEADER || length > table.size {
+        return Err(invalid("Invalid WIN_CERTIFICATE length"));
+    }
+    Ok(length)
+}
+
+/// Reads the PKCS#7 signature of the first `WIN_CERTIFICATE` entry
+pub fn read_signature<R: Read + Seek>(
+    reader: &mut R,
+    table: &CertificateTable,
+) -> std::io::Result<Vec<u8>> {
+    let length = first_entry_length(reader, table)?;
+    let mut signature = vec![0u8; (length - WIN_CERTIFICATE_HEADER) as usize];
+    reader.seek(SeekFrom::Start(table.offset + WIN_CERTIFICATE_HEADER))?;
+    reader.read_exact(&mut signature)?;
+    Ok(signature)
+}
+
+/// Replaces the PKCS#7 signature of the first `WIN_CERTIFICATE` entry with `signature`
+///
+/// The table has to be at the end of the file, which is where signing tools put it. Any further
+/// entries are kept and the table is resized in the security directory.
+pub fn replace_signature(
+    file: &mut File,
+    table: &CertificateTable,
+    signature: &[u8],
+) -> std::io::Result<()> {
+    if file.seek(SeekFrom::End(0))? != table.end() {
+        return Err(invalid("Certificate table is not at the end of the file"));
+    }
+
+    let mut old = vec![0u8; table.size as usize];
+    file.seek(SeekFrom::Start(table.offset))?;
+    file.read_exact(&mut old)?;
+    let length = first_entry_length(file, table)?;
+    let rest = &old[(length.next_multiple_of(8) as usize).min(old.len())..];
+
+    let entry_length = WIN_CERTIFICATE_HEADER as usize + signature.len();
+    let mut new = Vec::with_capacity(entry_length + 8 + rest.len());
+    new.extend_from_slice(
+        &u32::try_from(entry_length)
+            .map_err(|_| invalid("Signature too large"))?
+            .to_le_bytes(),
+    );
+    // Keep wRevision and wCertificateType
+    new.extend_from_slice(&old[4..8]);
+    new.extend_from_slice(signature);
+    new.resize(entry_length.next_multiple_of(8), 0);
+    new.extend_from_slice(rest);
+    let table_size = u32::try_from(new.len()).map_err(|_| invalid("Signature too large"))?;
+
+    file.seek(SeekFrom::Start(table.offset))?;
+    file.write_all(&new)?;
+    file.set_len(table.offset + new.len() as u64)?;
+    write_u32(file, table.directory_entry + 4, table_size)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::deletion_guard::TemporaryFileCleaner;
+
+    const SIGNED: &[u8] = include_bytes!("../tests/fixtures/signed.exe");
+    const UNSIGNED: &[u8] = include_bytes!("../tests/fixtures/unsigned.exe");
+
+    fn table(bytes: &[u8]) -> Option<CertificateTable> {
+        certificate_table(&mut Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn finds_certificate_table_of_signed_binary() {
+        let table = table(SIGNED).unwrap();
+        assert_eq!(table.offset, 512);
+        assert_eq!(table.end(), SIGNED.len() as u64);
+        assert_eq!(table.directory_entry, 0x40 + 24 + 112 + 4 * 8);
+    }
+
+    #[test]
+    fn unsigned_and_non_pe_files_have_no_certificate_table() {
+        assert_eq!(table(UNSIGNED), None);
+        assert_eq!(table(b""), None);
+        assert_eq!(table(&[0u8; 1024]), None);
+        assert_eq!(table(&SIGNED[..0x3e]), None);
+    }
+
+    #[test]
+    fn truncated_binaries_have_no_certificate_table() {
+        for len in [0x42, 0x80, 0x100, 600, SIGNED.len() - 1] {
+            assert_eq!(table(&SIGNED[..len]), None, "truncated to {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn reads_signature() {
+        let table = table(SIGNED).unwrap();
+        let signature = read_signature(&mut Cursor::new(SIGNED), &table).unwrap();
+        // The DER SEQUENCE of the PKCS#7 ContentInfo
+        assert_eq!(signature[0], 0x30);
+        assert_eq!(&SIGNED[520..520 + signature.len()], signature);
+    }
+
+    #[test]
+    fn replace_signature_round_trips() {
+        let tmp = TemporaryFileCleaner::new_with_extension("exe");
+        std::fs::write(tmp.file_path(), SIGNED).unwrap();
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .open(tmp.file_path())
+            .unwrap();
+
+        let original = read_signature(&mut file, &table(SIGNED).unwrap()).unwrap();
+        for signature in [vec![0xab; 5000], vec![0xcd; 3], original.clone()] {
+            let table = certificate_table(&mut file).unwrap().unwrap();
+            replace_signature(&mut file, &table, &signature).unwrap();
+
+            let table = certificate_table(&mut file).unwrap().unwrap();
+            assert_eq!(table.end(), file.metadata().unwrap().len());
+            assert_eq!(table.size % 8, 0);
+            assert_eq!(read_signature(&mut file, &table).unwrap(), signature);
+        }
+
+        assert_eq!(std::fs::read(tmp.file_path()).unwrap(), SIGNED);
+    }
+
+    #[test]
+    fn replace_signature_requires_table_at_end_of_file() {
+        let tmp = TemporaryFileCleaner::new_with_extension("exe");
+        std::fs::write(tmp.file_path(), [SIGNED, b"appended"].concat()).unwrap();
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .open(tmp.file_path())
+            .unwrap();
+
+        let table = certificate_table(&mut file).unwrap().unwrap();
+        assert!(replace_signature(&mut file, &table, &[0x30, 0]).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+use std::io::{Error, ErrorKind};
+
+/// OID 1.2.840.113549.1.7.2 (`signedData`) in DER
+const SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+
+const OID: u8 = 0x06;
+const OCTET_STRING: u8 = 0x04;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+/// `[0] EXPLICIT` content of the ContentInfo
+const CONTENT: u8 = 0xa0;
+/// `[1] IMPLICIT` unsigned attributes of a SignerInfo
+const UNSIGNED_ATTRS: u8 = 0xa1;
+
+fn invalid(msg: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid code signature: {}", msg),
+    )
+}
+
+/// A DER element borrowed from the signature
+struct Tlv<'a> {
+    tag: u8,
+    content: &'a [u8],
+    /// The whole element including tag and length
+    raw: &'a [u8],
+}
+
+/// Splits the first DER element off `data`
+fn next(data: &[u8]) -> std::io::Result<(Tlv<'_>, &[u8])> {
+    let [tag, first, rest @ ..] = data else {
+        return Err(invalid("truncated element"));
+    };
+    if tag & 0x1f == 0x1f {
+        return Err(invalid("unsupported tag"));
+    }
+
+    let (len, rest) = match *first {
+        len @ 0..=0x7f => (len as usize, rest),
+        // 0x80 is BER's indefinite length, which signatures don't use
+        long @ 0x81..=0x84 => {
+            let n = (long & 0x7f) as usize;
+            if rest.len() < n {
+                return Err(invalid("truncated length"));
+            }
+            let len = rest[..n].iter().fold(0, |len, b| len << 8 | *b as usize);
+            (len, &rest[n..])
+        }
+        _ => return Err(invalid("unsupported length")),
+    };
+    if rest.len() < len {
+        return Err(invalid("truncated element"));
+    }
+
+    let header = data.len() - rest.len();
+    let tlv = Tlv {
+        tag: *tag,
+        content: &rest[..len],
+        raw: &data[..header + len],
+    };
+    Ok((tlv, &rest[len..]))
+}
+
+fn children(mut content: &[u8]) -> std::io::Result<Vec<Tlv<'_>>> {
+    let mut children = Vec::new();
+    while !content.is_empty() {
+        let (child, rest) = next(content)?;
+        children.push(child);
+        content = rest;
+    }
+    Ok(children)
+}
+
+fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    if content.len() < 0x80 {
+        out.push(content.len() as u8);
+    } else {
+        let len = content.len().to_be_bytes();
+        let skip = len.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (len.len() - skip) as u8);
+        out.extend_from_slice(&len[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// Encodes `children` under `tag`, swapping the child at `index` for `replacement`
+fn replace(tag: u8, children: &[Tlv], index: usize, replacement: &[u8]) -> Vec<u8> {
+    let content = children
+        .iter()
+        .enumerate()
+        .flat_map(|(i, child)| if i == index { replacement } else { child.raw })
+        .copied()
+        .collect::<Vec<_>>();
+    encode(tag, &content)
+}
+
+/// The chain of elements from the ContentInfo down to the first SignerInfo
+struct Signature<'a> {
+    content_info: Vec<Tlv<'a>>,
+    signed_data: Vec<Tlv<'a>>,
+    signer_infos: Vec<Tlv<'a>>,
+    signer_info: Vec<Tlv<'a>>,
+}
+
+impl<'a> Signature<'a> {
+    fn parse(der: &'a [u8]) -> std::io::Result<Self> {
+        // Anything after the ContentInfo is padding and gets dropped when re-encoding
+        let (content_info, _) = next(der)?;
+        if content_info.tag != SEQUENCE {
+            return Err(invalid("expected a ContentInfo"));
+        }
+        let content_info = children(content_info.content)?;
+        let signed_data = match content_info.as_slice() {
+            [oid, content]
+                if oid.tag == OID && oid.content == SIGNED_DATA && content.tag == CONTENT =>
+            {
+                next(content.content)?.0
+            }
+            _ => return Err(invalid("not a PKCS#7 SignedData")),
+        };
+        if signed_data.tag != SEQUENCE {
+            return Err(invalid("expected a SignedData"));
+        }
+
+        let signed_data = children(signed_data.content)?;
+        let signer_infos = match signed_data.last() {
+            Some(signer_infos) if signer_infos.tag == SET => children(signer_infos.content)?,
+            _ => return Err(invalid("missing signer infos")),
+        };
+        let signer_info = match signer_infos.first() {
+            Some(signer_info) if signer_info.tag == SEQUENCE => children(signer_info.content)?,
+            _ => return Err(invalid("missing signer info")),
+        };
+
+        Ok(Self {
+            content_info,
+            signed_data,
+            signer_infos,
+            signer_info,
+        })
+    }
+
+    /// Unsigned attributes of the first signer with their type
+    fn attributes(&self) -> std::io::Result<Vec<(&'a [u8], Tlv<'a>)>> {
+        let Some(attrs) = self.signer_info.iter().find(|t| t.tag == UNSIGNED_ATTRS) else {
+            return Ok(Vec::new());
+        };
+
+        children(attrs.content)?
+            .into_iter()
+            .map(|attr| match children(attr.content)?.as_slice() {
+                [oid, values] if oid.tag == OID && values.tag == SET => Ok((oid.content, attr)),
+                _ => Err(invalid("malformed attribute")),
+            })
+            .collect()
+    }
+
+    /// Re-encodes the signature with `signer_info` as the content of the first SignerInfo
+    fn encode(&self, signer_info: &[u8]) -> Vec<u8> {
+        let signer_info = encode(SEQUENCE, signer_info);
+        let signer_infos = replace(SET, &self.signer_infos, 0, &signer_info);
+        let last = self.signed_data.len() - 1;
+        let signed_data = replace(SEQUENCE, &self.signed_data, last, &signer_infos);
+        let content = encode(CONTENT, &signed_data);
+        replace(SEQUENCE, &self.content_info, 1, &content)
+    }
+}
+
+/// Reads the octet string stored in the unsigned attribute `oid` of the first signer
+pub fn unsigned_attribute(der: &[u8], oid: &[u8]) -> std::io::Result<Option<Vec<u8>>> {
+    let signature = Signature::parse(der)?;
+    let Some((_, attr)) = signature.attributes()?.into_iter().find(|(t, _)| *t == oid) else {
+        return Ok(None);
+    };
+
+    let values = children(attr.content)?.pop().unwrap();
+    match children(values.content)?.first() {
+        Some(value) if value.tag == OCTET_STRING => Ok(Some(value.content.to_vec())),
+        _ => Err(invalid("attribute value is not an octet string")),
+    }
+}
+
+/// Sets or removes the unsigned attribute `oid` of the first signer, returns the new signature
+///
+/// Unsigned attributes aren't covered by the signature, which is also where timestamps and
+/// nested signatures live. Other attributes are kept as they are.
+pub fn set_unsigned_attribute(
+    der: &[u8],
+    oid: &[u8],
+    value: Option<&[u8]>,
+) -> std::io::Result<Vec<u8>> {
+    let signature = Signature::parse(der)?;
+    let mut attrs = signature
+        .attributes()?
+        .into_iter()
+        .filter(|(t, _)| *t != oid)
+        .flat_map(|(_, attr)| attr.raw)
+        .copied()
+        .collect::<Vec<_>>();
+    if let Some(value) = value {
+        let values = encode(SET, &encode(OCTET_STRING, value));
+        attrs.extend(encode(SEQUENCE, &[encode(OID, oid), values].concat()));
+    }
+
+    let mut signer_info = signature
+        .signer_info
+        .iter()
+        .filter(|t| t.tag != UNSIGNED_ATTRS)
+        .flat_map(|t| t.raw)
+        .copied()
+        .collect::<Vec<_>>();
+    if !attrs.is_empty() {
+        signer_info.extend(encode(UNSIGNED_ATTRS, &attrs));
+    }
+
+    Ok(signature.encode(&signer_info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OID_A: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0x01];
+    const OID_B: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0x02];
+
+    /// The PKCS#7 blob of the signed fixture
+    fn signature() -> Vec<u8> {
+        let signed = include_bytes!("../tests/fixtures/signed.exe");
+        let (tlv, _) = next(&signed[520..]).unwrap();
+        tlv.raw.to_vec()
+    }
+
+    #[test]
+    fn fixture_has_no_unsigned_attributes() {
+        assert_eq!(unsigned_attribute(&signature(), OID_A).unwrap(), None);
+    }
+
+    #[test]
+    fn sets_reads_and_removes_attributes() {
+        let original = signature();
+        let with_a = set_unsigned_attribute(&original, OID_A, Some(b"first")).unwrap();
+        let with_both = set_unsigned_attribute(&with_a, OID_B, Some(b"second")).unwrap();
+        assert_eq!(
+            unsigned_attribute(&with_both, OID_A).unwrap().unwrap(),
+            b"first"
+        );
+        assert_eq!(
+            unsigned_attribute(&with_both, OID_B).unwrap().unwrap(),
+            b"second"
+        );
+
+        let replaced = set_unsigned_attribute(&with_both, OID_A, Some(b"changed")).unwrap();
+        assert_eq!(
+            unsigned_attribute(&replaced, OID_A).unwrap().unwrap(),
+            b"changed"
+        );
+        assert_eq!(
+            unsigned_attribute(&replaced, OID_B).unwrap().unwrap(),
+            b"second"
+        );
+
+        let only_b = set_unsigned_attribute(&replaced, OID_A, None).unwrap();
+        assert_eq!(unsigned_attribute(&only_b, OID_A).unwrap(), None);
+        assert_eq!(
+            unsigned_attribute(&only_b, OID_B).unwrap().unwrap(),
+            b"second"
+        );
+
+        let none = set_unsigned_attribute(&only_b, OID_B, None).unwrap();
+        assert_eq!(none, original);
+    }
+
+    #[test]
+    fn result_is_a_single_der_element() {
+        // Trailing data after the ContentInfo would fail `EnableCertPaddingCheck`
+        let padded = [signature(), vec![0; 7]].concat();
+        let value = vec![0x5a; 70_000];
+        let signed = set_unsigned_attribute(&padded, OID_A, Some(&value)).unwrap();
+
+        let (tlv, rest) = next(&signed).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(tlv.raw.len(), signed.len());
+        assert_eq!(unsigned_attribute(&signed, OID_A).unwrap().unwrap(), value);
+    }
+
+    #[test]
+    fn signed_attributes_are_untouched() {
+        let original = signature();
+        let signed = set_unsigned_attribute(&original, OID_A, Some(b"value")).unwrap();
+
+        let before = Signature::parse(&original).unwrap();
+        let after = Signature::parse(&signed).unwrap();
+        assert_eq!(before.signed_data.len(), after.signed_data.len());
+        for (a, b) in before
+            .signed_data
+            .iter()
+            .zip(&after.signed_data)
+            .rev()
+            .skip(1)
+        {
+            assert_eq!(a.raw, b.raw);
+        }
+        for (a, b) in before.signer_info.iter().zip(&after.signer_info) {
+            assert_eq!(a.raw, b.raw);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        let original = signature();
+        for bad in [
+            &[][..],
+            &[0x30, 0x80, 0x00, 0x00],
+            &[0x30, 0x05, 0x06, 0x01],
+            &original[..original.len() - 1],
+            &[0x30, 0x03, 0x02, 0x01, 0x01],
+        ] {
+            assert!(unsigned_attribute(bad, OID_A).is_err());
+            assert!(set_unsigned_attribute(bad, OID_A, Some(b"x")).is_err());
+        }
+    }
+}
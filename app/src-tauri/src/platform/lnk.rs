@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+/// Size of the ShellLinkHeader, also stored in its first field
+const HEADER_SIZE: usize = 0x4c;
+
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x01;
+const HAS_LINK_INFO: u32 = 0x02;
+const HAS_NAME: u32 = 0x04;
+const HAS_RELATIVE_PATH: u32 = 0x08;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const IS_UNICODE: u32 = 0x80;
+/// LinkInfo flag for a `LocalBasePath` being present
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x01;
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Reads a NUL terminated string in the system code page, non-ASCII characters are replaced
+fn c_string(data: &[u8]) -> Option<String> {
+    let end = data.iter().position(|b| *b == 0)?;
+    Some(String::from_utf8_lossy(&data[..end]).to_string())
+}
+
+/// Reads the local path of a LinkInfo structure, `None` for network shares
+fn local_path(info: &[u8]) -> Option<String> {
+    if u32_at(info, 8)? & VOLUME_ID_AND_LOCAL_BASE_PATH == 0 {
+        return None;
+    }
+
+    let base = c_string(info.get(u32_at(info, 0x10)? as usize..)?)?;
+    let suffix = c_string(info.get(u32_at(info, 0x18)? as usize..)?)?;
+    Some(base + &suffix)
+}
+
+/// The parts of a shortcut this module reads
+struct Link {
+    /// Absolute target path from the LinkInfo
+    local: Option<String>,
+    relative_path: Option<String>,
+    working_dir: Option<String>,
+    arguments: Option<String>,
+}
+
+fn parse(data: &[u8]) -> Option<Link> {
+    if u32_at(data, 0)? as usize != HEADER_SIZE {
+        return None;
+    }
+
+    let flags = u32_at(data, 0x14)?;
+    let mut offset = HEADER_SIZE;
+    if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+        offset += 2 + u16_at(data, offset)? as usize;
+    }
+
+    let mut local = None;
+    if flags & HAS_LINK_INFO != 0 {
+        let size = u32_at(data, offset)? as usize;
+        local = local_path(data.get(offset..offset + size)?);
+        offset += size;
+    }
+
+    // The strings that follow are all optional, but always in this order
+    let mut strings = Vec::new();
+    for flag in [HAS_NAME, HAS_RELATIVE_PATH, HAS_WORKING_DIR, HAS_ARGUMENTS] {
+        if flags & flag == 0 {
+            strings.push(None);
+            continue;
+        }
+
+        let Some(count) = u16_at(data, offset).map(usize::from) else {
+            break;
+        };
+        offset += 2;
+        let value = if flags & IS_UNICODE != 0 {
+            let Some(bytes) = data.get(offset..offset + count * 2) else {
+                break;
+            };
+            let units = bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+            offset += count * 2;
+            String::from_utf16(&units).ok()
+        } else {
+            let Some(bytes) = data.get(offset..offset + count) else {
+                break;
+            };
+            offset += count;
+            Some(String::from_utf8_lossy(bytes).to_string())
+        };
+        strings.push(value);
+    }
+    // Truncated strings are left out, the header and LinkInfo may still be enough
+    strings.resize(4, None);
+
+    let mut strings = strings.into_iter().skip(1);
+    Some(Link {
+        local,
+        relative_path: strings.next().flatten(),
+        working_dir: strings.next().flatten(),
+        arguments: strings.next().flatten(),
+    })
+}
+
+/// Reads the target of a Windows shortcut (MS-SHLLINK), `None` if it can't be determined
+///
+/// Shortcuts made by Explorer carry the absolute path in their LinkInfo. mslnk doesn't write one,
+/// but stores the file name relative to the working directory, which is the target's folder.
+pub fn target(data: &[u8]) -> Option<PathBuf> {
+    let link = parse(data)?;
+    if let Some(local) = link.local {
+        return Some(PathBuf::from(local));
+    }
+
+    let relative = link.relative_path?;
+    let name = relative
+        .strip_prefix("./")
+        .or_else(|| relative.strip_prefix(".\\"))?;
+    Some(PathBuf::from(link.working_dir?).join(name))
+}
+
+/// Reads the command line arguments of a Windows shortcut
+pub fn arguments(data: &[u8]) -> Option<String> {
+    parse(data)?.arguments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_shortcuts_written_by_mslnk() {
+        let lnk = include_bytes!("../../tests/fixtures/mslnk.lnk");
+        let expected = PathBuf::from(r"C:\Users\Player\AppData\Local\Programs\PrismLauncher")
+            .join("prismlauncher.exe");
+        assert_eq!(target(lnk), Some(expected));
+    }
+
+    #[test]
+    fn prefers_the_local_path_of_explorer_shortcuts() {
+        let lnk = include_bytes!("../../tests/fixtures/explorer.lnk");
+        assert_eq!(
+            target(lnk),
+            Some(PathBuf::from(r"C:\Games\Prism\prismlauncher.exe"))
+        );
+    }
+
+    #[test]
+    fn reads_arguments() {
+        let lnk = include_bytes!("../../tests/fixtures/mslnk.lnk");
+        assert_eq!(arguments(lnk).as_deref(), Some("-l \"Test Pack\""));
+    }
+
+    #[test]
+    fn rejects_other_and_truncated_files() {
+        let lnk = include_bytes!("../../tests/fixtures/mslnk.lnk");
+        assert_eq!(target(b""), None);
+        assert_eq!(target(b"[Desktop Entry]\nExec=prismlauncher\n"), None);
+        for len in [4, HEADER_SIZE, HEADER_SIZE + 10, 200] {
+            assert_eq!(target(&lnk[..len]), None, "{}", len);
+        }
+    }
+}
@@ -1,3 +1,9 @@
+use std::path::{Path, PathBuf};
+
+use directories::{BaseDirs, ProjectDirs, UserDirs};
+
+#[cfg(any(target_os = "windows", test))]
+mod lnk;
 #[cfg(target_os = "windows")]
 mod windows;
 #[cfg(target_os = "windows")]
@@ -8,3 +14,29 @@ mod unix;
 
 #[cfg(not(target_os = "windows"))]
 pub use unix::*;
+
+/// Per-user directories the installer writes to outside of PrismLauncher's data directory
+///
+/// Installs take them from the environment, tests pass temporary ones instead.
+#[derive(Debug, Clone, Default)]
+pub struct UserPaths {
+    pub home: Option<PathBuf>,
+    /// `~/.local/share` on Linux, the roaming AppData on Windows
+    pub data: Option<PathBuf>,
+    pub desktop: Option<PathBuf>,
+    /// Where downloads are cached, `None` downloads without caching
+    pub cache: Option<PathBuf>,
+}
+
+impl UserPaths {
+    pub fn current() -> Self {
+        let base_dirs = BaseDirs::new();
+        Self {
+            home: base_dirs.as_ref().map(|b| b.home_dir().to_path_buf()),
+            data: base_dirs.as_ref().map(|b| b.data_dir().to_path_buf()),
+            desktop: UserDirs::new().and_then(|u| u.desktop_dir().map(Path::to_path_buf)),
+            cache: ProjectDirs::from("me", "sshcrack", "modpack-installer")
+                .map(|dirs| dirs.cache_dir().join("downloads")),
+        }
+    }
+}
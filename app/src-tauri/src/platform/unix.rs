@@ -1,26 +1,499 @@
+use async_stream::stream;
+use directories::BaseDirs;
+use flate2::read::GzDecoder;
+use futures_core::Stream;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tar::Archive;
 
-pub async fn create_shortcut(
-    _prism_exec: &Path,
-    _instance_name: &str,
-    _config_name: &str,
-    _shortcut_icon: &Path,
+use super::UserPaths;
+
+const FLATPAK_APP_ID: &str = "org.prismlauncher.PrismLauncher";
+const EXEC_NAMES: [&str; 2] = ["prismlauncher", "PrismLauncher"];
+
+/// Processes on unix always inherit the terminal they were started from
+pub fn attach_console() {}
+
+/// Architecture of the machine, which on unix is always the one the installer was built for
+pub fn native_arch() -> &'static str {
+    env::consts::ARCH
+}
+
+/// Path of the launcher executable inside an extracted portable build
+pub fn portable_exec(dir: &Path) -> PathBuf {
+    // The portable tarball ships a wrapper script that sets up the bundled libraries
+    dir.join("PrismLauncher")
+}
+
+/// Counts the compressed bytes read so extraction progress can be reported
+struct CountingReader<R> {
+    inner: R,
+    read: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+fn extract_tar_gz(
+    archive: &Path,
+    dest: &Path,
+    // Returns false once nobody listens anymore, which stops the extraction
+    on_progress: impl Fn(f32, String) -> bool,
 ) -> Result<(), anyhow::Error> {
-    log::warn!("Shortcut creation is not supported on this platform.");
+    let file = File::open(archive)?;
+    let total = file.metadata()?.len().max(1);
+    let read = Arc::new(AtomicU64::new(0));
+
+    let reader = CountingReader {
+        inner: file,
+        read: read.clone(),
+    };
+    let mut tar = Archive::new(GzDecoder::new(reader));
+
+    std::fs::create_dir_all(dest)?;
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.display().to_string();
+
+        entry.unpack_in(dest)?;
+
+        let percentage = read.load(Ordering::Relaxed) as f32 / total as f32;
+        if !on_progress(percentage.min(1.0), format!("Extracting {}", name)) {
+            anyhow::bail!("Extraction cancelled");
+        }
+    }
+
     Ok(())
 }
 
+pub fn extract_portable(
+    archive: &Path,
+    dest: &Path,
+) -> impl Stream<Item = Result<(f32, String), anyhow::Error>> {
+    let archive = archive.to_owned();
+    let dest = dest.to_owned();
+    stream! {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let task = tokio::task::spawn_blocking(move || {
+            extract_tar_gz(&archive, &dest, |percentage, msg| {
+                tx.send((percentage, msg)).is_ok()
+            })
+        });
+
+        while let Some(progress) = rx.recv().await {
+            yield Ok(progress);
+        }
+
+        match task.await {
+            Ok(Ok(())) => yield Ok((1.0, "Extracted PrismLauncher".to_string())),
+            Ok(Err(e)) => yield Err(e),
+            Err(e) => yield Err(anyhow::anyhow!("Extraction task failed: {}", e)),
+        }
+    }
+}
+
+/// Installs the downloaded AppImage into `~/.local/bin` under its asset name and returns its path
+pub async fn install_launcher(
+    paths: &UserPaths,
+    installer: &Path,
+    asset_name: &str,
+) -> Result<Option<PathBuf>, String> {
+    let home = paths
+        .home
+        .as_ref()
+        .ok_or("Failed to determine home directory")?;
+    let bin_dir = home.join(".local/bin");
+    let file_name = Path::new(asset_name)
+        .file_name()
+        .ok_or_else(|| format!("Invalid PrismLauncher asset name '{}'", asset_name))?;
+    let target = bin_dir.join(file_name);
+
+    tokio::fs::create_dir_all(&bin_dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", bin_dir.display(), e))?;
+
+    tokio::fs::copy(installer, &target)
+        .await
+        .map_err(|e| format!("Failed to install PrismLauncher AppImage: {}", e))?;
+
+    tokio::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o755))
+        .await
+        .map_err(|e| format!("Failed to make PrismLauncher AppImage executable: {}", e))?;
+
+    log::info!("Installed PrismLauncher AppImage to {:?}", target);
+    Ok(Some(target))
+}
+
+/// Quotes an argument for the `Exec` key of a desktop entry
+fn quote_exec_arg(arg: &str) -> String {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Splits the `Exec` key of a desktop entry into its arguments, reversing [`quote_exec_arg`]
+fn exec_args(exec: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = exec.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            break;
+        };
+
+        let mut arg = String::new();
+        if first == '"' {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => arg.extend(chars.next()),
+                    '%' => {
+                        chars.next();
+                        arg.push('%');
+                    }
+                    '"' => break,
+                    _ => arg.push(c),
+                }
+            }
+        } else {
+            arg.push(first);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+
+    args
+}
+
+/// Arguments of a desktop entry, `None` for files that aren't desktop entries
+fn desktop_entry_args(path: &Path) -> Option<Vec<String>> {
+    if path.extension()? != "desktop" {
+        return None;
+    }
+
+    let entry = std::fs::read_to_string(path).ok()?;
+    let exec = entry.lines().find_map(|l| l.strip_prefix("Exec="))?;
+    Some(exec_args(exec))
+}
+
+/// Executable a desktop entry launches, `None` for files that aren't desktop entries
+pub fn shortcut_target(path: &Path) -> Option<PathBuf> {
+    desktop_entry_args(path)?
+        .into_iter()
+        .next()
+        .map(PathBuf::from)
+}
+
+/// Instance a desktop entry launches with `-l`
+pub fn shortcut_instance(path: &Path) -> Option<String> {
+    let args = desktop_entry_args(path)?;
+    let flag = args.iter().position(|a| a == "-l")?;
+    args.get(flag + 1).cloned()
+}
+
+/// File name for the desktop entry, without characters that would break the path
+fn desktop_file_name(config_name: &str) -> String {
+    let name = config_name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+
+    format!("{}.desktop", name)
+}
+
+pub async fn create_shortcut(
+    paths: &UserPaths,
+    prism_exec: &Path,
+    instance_name: &str,
+    config_name: &str,
+    shortcut_icon: &Path,
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let data_dir = paths
+        .data
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No home directory found"))?;
+
+    // The hicolor theme expects square icons sorted by size, so the png next to the .ico is scaled
+    let icon_stem = shortcut_icon
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow::anyhow!("Invalid shortcut icon path"))?;
+    let icon_name = format!("prismlauncher-{}", icon_stem);
+    let icon_dir = data_dir.join("icons/hicolor/256x256/apps");
+    tokio::fs::create_dir_all(&icon_dir).await?;
+
+    let icon = image::open(shortcut_icon.with_extension("png"))?;
+    let icon_path = icon_dir.join(format!("{}.png", icon_name));
+    icon.resize_to_fill(256, 256, image::imageops::FilterType::Lanczos3)
+        .save(&icon_path)?;
+    log::info!("Installed shortcut icon into {:?}", icon_dir);
+    let mut created = vec![icon_path];
+
+    let entry = format!(
+        "[Desktop Entry]\n\
+        Type=Application\n\
+        Version=1.0\n\
+        Name={name}\n\
+        Comment=Play {name} with PrismLauncher\n\
+        Exec={exec} -l {instance}\n\
+        Icon={icon}\n\
+        Terminal=false\n\
+        Categories=Game;\n",
+        name = config_name,
+        exec = quote_exec_arg(&prism_exec.to_string_lossy()),
+        instance = quote_exec_arg(instance_name),
+        icon = icon_name,
+    );
+
+    let file_name = desktop_file_name(config_name);
+    let mut targets = vec![data_dir.join("applications")];
+    match &paths.desktop {
+        Some(desktop_dir) => targets.push(desktop_dir.clone()),
+        None => log::warn!("No desktop directory found, skipping desktop entry creation"),
+    }
+
+    for dir in targets {
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let path = dir.join(&file_name);
+        tokio::fs::write(&path, &entry).await?;
+        // Desktop environments refuse to launch entries on the desktop that aren't executable
+        tokio::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).await?;
+        log::info!("Created desktop entry: {:?}", path);
+        created.push(path);
+    }
+
+    Ok(created)
+}
+
+/// Paths `create_shortcut` writes to, used to clean up installs that have no manifest
+pub fn shortcut_paths(paths: &UserPaths, config_name: &str, icon_key: &str) -> Vec<PathBuf> {
+    let Some(data_dir) = &paths.data else {
+        return Vec::new();
+    };
+
+    let file_name = desktop_file_name(config_name);
+    let mut shortcuts = vec![
+        data_dir.join(format!(
+            "icons/hicolor/256x256/apps/prismlauncher-{}.png",
+            icon_key
+        )),
+        data_dir.join("applications").join(&file_name),
+    ];
+    if let Some(desktop_dir) = &paths.desktop {
+        shortcuts.push(desktop_dir.join(&file_name));
+    }
+
+    shortcuts
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Returns the first AppImage in `dir` whose name looks like a PrismLauncher build
+fn find_appimage(dir: &Path) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(dir).ok()?;
+    entries.filter_map(|e| e.ok()).map(|e| e.path()).find(|p| {
+        let name = p
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        name.starts_with("prismlauncher") && name.ends_with(".appimage") && is_executable(p)
+    })
+}
+
+fn flatpak_exports() -> Vec<PathBuf> {
+    let mut exports = vec![PathBuf::from("/var/lib/flatpak/exports/bin").join(FLATPAK_APP_ID)];
+    if let Some(base_dirs) = BaseDirs::new() {
+        exports.push(
+            base_dirs
+                .data_dir()
+                .join("flatpak/exports/bin")
+                .join(FLATPAK_APP_ID),
+        );
+    }
+
+    exports
+}
+
+fn flatpak_data_dir() -> Option<PathBuf> {
+    BaseDirs::new().map(|b| {
+        b.home_dir()
+            .join(".var/app")
+            .join(FLATPAK_APP_ID)
+            .join("data/PrismLauncher")
+    })
+}
+
+fn xdg_data_dir() -> Option<PathBuf> {
+    BaseDirs::new().map(|b| b.data_dir().join("PrismLauncher"))
+}
+
 pub fn get_prism_launcher_exec() -> Result<Option<PathBuf>, String> {
-    log::warn!("PrismLauncher executable detection is not supported on this platform.");
+    let mut search_dirs = env::var_os("PATH")
+        .map(|p| env::split_paths(&p).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    search_dirs.push(PathBuf::from("/usr/bin"));
+    search_dirs.push(PathBuf::from("/usr/local/bin"));
+    if let Some(base_dirs) = BaseDirs::new() {
+        search_dirs.push(base_dirs.home_dir().join(".local/bin"));
+    }
+
+    for dir in &search_dirs {
+        for name in EXEC_NAMES {
+            let candidate = dir.join(name);
+            if is_executable(&candidate) {
+                log::info!("Found PrismLauncher executable at {:?}", candidate);
+                return Ok(Some(candidate));
+            }
+        }
+    }
+
+    let mut appimage_dirs = Vec::new();
+    if let Some(base_dirs) = BaseDirs::new() {
+        let home = base_dirs.home_dir();
+        appimage_dirs.push(home.join("Applications"));
+        appimage_dirs.push(home.join(".local/bin"));
+        appimage_dirs.push(home.join("Downloads"));
+    }
+    appimage_dirs.push(PathBuf::from("/opt"));
+
+    if let Some(appimage) = appimage_dirs.iter().find_map(|d| find_appimage(d)) {
+        log::info!("Found PrismLauncher AppImage at {:?}", appimage);
+        return Ok(Some(appimage));
+    }
+
+    if let Some(flatpak) = flatpak_exports().into_iter().find(|p| is_executable(p)) {
+        log::info!("Found PrismLauncher flatpak at {:?}", flatpak);
+        return Ok(Some(flatpak));
+    }
+
+    log::info!("No PrismLauncher executable found");
     Ok(None)
 }
 
+/// Returns the data directory a given PrismLauncher executable will use, even if it doesn't exist yet
+pub fn get_prism_launcher_data_for(prism_exec: &Path) -> Option<PathBuf> {
+    let exec_dir = prism_exec.parent()?;
+
+    // Portable installs keep their config (and thus all data) next to the binary
+    if exec_dir.join("prismlauncher.cfg").exists() || exec_dir.join("portable.txt").exists() {
+        return Some(exec_dir.to_path_buf());
+    }
+
+    let is_flatpak = prism_exec
+        .file_name()
+        .map(|n| n == FLATPAK_APP_ID)
+        .unwrap_or(false);
+    if is_flatpak {
+        return flatpak_data_dir();
+    }
+
+    xdg_data_dir()
+}
+
 pub fn get_prism_launcher_data() -> Result<Option<PathBuf>, String> {
-    get_prism_launcher_exec()?
-        .map(|e| e.parent().map(|p| p.to_path_buf()))
-        .ok_or_else(|| {
-            log::warn!("PrismLauncher executable not found, returning None for data directory.");
+    let exec_data = get_prism_launcher_exec()?.and_then(|e| get_prism_launcher_data_for(&e));
 
-            "PrismLauncher executable not found".to_string()
-        })
+    let data = exec_data
+        .into_iter()
+        .chain(xdg_data_dir())
+        .chain(flatpak_data_dir())
+        .find(|d| d.join("prismlauncher.cfg").exists());
+
+    if data.is_none() {
+        log::warn!("PrismLauncher data directory not found");
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn installs_appimage_under_its_asset_name() {
+        let tmp = test_util::temp_dir();
+        let home = tmp.file_path();
+        let paths = test_util::user_paths(home);
+        let download = home.join("download");
+        std::fs::write(&download, b"appimage").unwrap();
+
+        for (asset, expected) in [
+            (
+                "PrismLauncher-Linux-aarch64.AppImage",
+                "PrismLauncher-Linux-aarch64.AppImage",
+            ),
+            (
+                "launcher/PrismLauncher-Linux-x86_64.AppImage",
+                "PrismLauncher-Linux-x86_64.AppImage",
+            ),
+        ] {
+            let installed =
+                tauri::async_runtime::block_on(install_launcher(&paths, &download, asset))
+                    .unwrap()
+                    .unwrap();
+            assert_eq!(installed, home.join(".local/bin").join(expected));
+            assert_eq!(std::fs::read(&installed).unwrap(), b"appimage");
+            assert!(is_executable(&installed));
+        }
+        assert!(tauri::async_runtime::block_on(install_launcher(&paths, &download, "..")).is_err());
+    }
+
+    #[test]
+    fn reads_back_quoted_exec_args() {
+        let exec = format!(
+            "{} -l {}",
+            quote_exec_arg("/opt/Prism Launcher/prismlauncher"),
+            quote_exec_arg(r#"Pack "100%" $HOME\"#)
+        );
+        assert_eq!(
+            exec_args(&exec),
+            [
+                "/opt/Prism Launcher/prismlauncher",
+                "-l",
+                r#"Pack "100%" $HOME\"#
+            ]
+        );
+        assert_eq!(
+            exec_args("prismlauncher  -l Pack"),
+            ["prismlauncher", "-l", "Pack"]
+        );
+    }
 }
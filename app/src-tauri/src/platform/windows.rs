@@ -1,36 +1,151 @@
-use directories::{BaseDirs, UserDirs};
+use async_stream::stream;
+use download_extract_progress::extract_zip;
+use futures_core::Stream;
+use futures_util::{pin_mut, StreamExt};
 use mslnk::ShellLink;
 use registry::{Hive, Security};
 use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+use windows_sys::Win32::System::SystemInformation::{
+    IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_ARM64, IMAGE_FILE_MACHINE_UNKNOWN,
+};
+use windows_sys::Win32::System::Threading::{GetCurrentProcess, IsWow64Process2};
+
+use super::UserPaths;
+
+/// Release builds use the windows subsystem, so stdout has to be hooked up to the calling terminal
+pub fn attach_console() {
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+/// Architecture of the machine, which differs from the installer's when it runs emulated on ARM
+pub fn native_arch() -> &'static str {
+    let mut process = IMAGE_FILE_MACHINE_UNKNOWN;
+    let mut native = IMAGE_FILE_MACHINE_UNKNOWN;
+    let ok = unsafe { IsWow64Process2(GetCurrentProcess(), &mut process, &mut native) };
+
+    if ok != 0 && native == IMAGE_FILE_MACHINE_ARM64 {
+        "aarch64"
+    } else if ok != 0 && native == IMAGE_FILE_MACHINE_AMD64 {
+        "x86_64"
+    } else {
+        std::env::consts::ARCH
+    }
+}
+
+/// Path of the launcher executable inside an extracted portable build
+pub fn portable_exec(dir: &Path) -> PathBuf {
+    dir.join("prismlauncher.exe")
+}
+
+pub fn extract_portable(
+    archive: &Path,
+    dest: &Path,
+) -> impl Stream<Item = Result<(f32, String), anyhow::Error>> {
+    let archive = archive.to_owned();
+    let dest = dest.to_owned();
+    stream! {
+        let extract = extract_zip(&archive, &dest).await;
+        pin_mut!(extract);
+
+        while let Some(res) = extract.next().await {
+            yield res.map_err(|e| anyhow::anyhow!("{}", e));
+        }
+    }
+}
+
+/// Runs the downloaded PrismLauncher setup and returns the installed executable
+pub async fn install_launcher(
+    _paths: &UserPaths,
+    installer: &Path,
+    _asset_name: &str,
+) -> Result<Option<PathBuf>, String> {
+    // Dropping the future on cancellation kills the installer instead of leaving it running
+    let out = Command::new(installer)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run PrismLauncher installer: {}", e))?;
+
+    let status = out.status;
+    if !status.success() {
+        return Err(format!(
+            "PrismLauncher installer exited with code: {}",
+            status.code().unwrap_or(-1)
+        ));
+    }
+
+    get_prism_launcher_exec()
+}
 
 pub async fn create_shortcut(
+    paths: &UserPaths,
     prism_exec: &Path,
     instance_name: &str,
     config_name: &str,
     shortcut_icon: &Path,
-) -> Result<(), anyhow::Error> {
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut created = Vec::new();
     let mut lnk = ShellLink::new(prism_exec)?;
     lnk.set_arguments(Some(format!("-l \"{}\"", instance_name)));
     lnk.set_icon_location(Some(shortcut_icon.to_string_lossy().to_string()));
 
-    if let Some(user_dirs) = UserDirs::new() {
-        if let Some(desktop_dir) = user_dirs.desktop_dir() {
-            lnk.create_lnk(desktop_dir.join(format!("{}.lnk", config_name)))?;
-            log::info!("Created shortcut on desktop: {:?}", desktop_dir);
-        } else {
-            log::warn!("No desktop directory found, skipping link creation");
-        }
+    if let Some(desktop_dir) = &paths.desktop {
+        let path = desktop_dir.join(format!("{}.lnk", config_name));
+        lnk.create_lnk(&path)?;
+        log::info!("Created shortcut on desktop: {:?}", desktop_dir);
+        created.push(path);
+    } else {
+        log::warn!("No desktop directory found, skipping link creation");
     }
 
-    if let Some(base_dirs) = BaseDirs::new() {
-        let app_data = base_dirs
-            .data_dir()
-            .join("Microsoft/Windows/Start Menu/Programs");
-        lnk.create_lnk(app_data.join(format!("{}.lnk", config_name)))?;
+    if let Some(data_dir) = &paths.data {
+        let app_data = data_dir.join("Microsoft/Windows/Start Menu/Programs");
+        let path = app_data.join(format!("{}.lnk", config_name));
+        lnk.create_lnk(&path)?;
         log::info!("Created shortcut in app data: {:?}", app_data);
+        created.push(path);
+    }
+
+    Ok(created)
+}
+
+/// Paths `create_shortcut` writes to, used to clean up installs that have no manifest
+pub fn shortcut_paths(paths: &UserPaths, config_name: &str, _icon_key: &str) -> Vec<PathBuf> {
+    let mut shortcuts = Vec::new();
+    if let Some(desktop_dir) = &paths.desktop {
+        shortcuts.push(desktop_dir.join(format!("{}.lnk", config_name)));
+    }
+
+    if let Some(data_dir) = &paths.data {
+        let start_menu = data_dir.join("Microsoft/Windows/Start Menu/Programs");
+        shortcuts.push(start_menu.join(format!("{}.lnk", config_name)));
+    }
+
+    shortcuts
+}
+
+/// Executable a shortcut launches, `None` for files that aren't shortcuts
+pub fn shortcut_target(path: &Path) -> Option<PathBuf> {
+    if path.extension()? != "lnk" {
+        return None;
     }
 
-    Ok(())
+    super::lnk::target(&std::fs::read(path).ok()?)
+}
+
+/// Instance a shortcut launches with `-l`
+pub fn shortcut_instance(path: &Path) -> Option<String> {
+    if path.extension()? != "lnk" {
+        return None;
+    }
+
+    let arguments = super::lnk::arguments(&std::fs::read(path).ok()?)?;
+    let instance = arguments.trim().strip_prefix("-l")?.trim();
+    Some(instance.trim_matches('"').to_string())
 }
 
 pub fn get_prism_launcher_exec() -> Result<Option<PathBuf>, String> {
@@ -71,6 +186,11 @@ pub fn get_prism_launcher_exec() -> Result<Option<PathBuf>, String> {
     Ok(Some(PathBuf::from(val)))
 }
 
+/// Returns the data directory a given PrismLauncher executable will use
+pub fn get_prism_launcher_data_for(prism_exec: &Path) -> Option<PathBuf> {
+    prism_exec.parent().map(|p| p.to_path_buf())
+}
+
 pub fn get_prism_launcher_data() -> Result<Option<PathBuf>, String> {
     get_prism_launcher_exec()?
         .map(|e| e.parent().map(|p| p.to_path_buf()))
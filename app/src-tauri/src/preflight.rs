@@ -0,0 +1,160 @@
+use std::path::Path;
+
+use futures_util::future::{join, join_all};
+use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::{Client, Response, StatusCode};
+use tokio::time::timeout;
+
+use crate::cache;
+use crate::download::DownloadPolicy;
+use crate::offline::OfflinePayload;
+use crate::pack;
+use crate::util::ModpackConfig;
+
+/// A remote resource the install needs and what it's expected to be
+struct Resource {
+    what: &'static str,
+    url: String,
+    /// Accepted `Content-Type` prefixes, anything goes if empty
+    content_types: &'static [&'static str],
+}
+
+/// Servers often label binary files as generic octet streams, which never counts against them
+const GENERIC_TYPES: [&str; 2] = ["application/octet-stream", "binary/octet-stream"];
+
+async fn head(client: &Client, url: &str, policy: &DownloadPolicy) -> Result<Response, String> {
+    let send = |req: reqwest::RequestBuilder| async move {
+        match timeout(policy.stall_timeout, req.send()).await {
+            Ok(res) => res.map_err(|e| e.to_string()),
+            Err(_) => Err("timed out".to_string()),
+        }
+    };
+
+    let res = send(client.head(url)).await?;
+    // Some servers only implement GET, the body is dropped without being read
+    match res.status() {
+        StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => send(client.get(url)).await,
+        _ => Ok(res),
+    }
+}
+
+/// Checks a single resource, returns what's wrong with it
+async fn check(
+    client: &Client,
+    resource: &Resource,
+    policy: &DownloadPolicy,
+    cache_dir: Option<&Path>,
+) -> Option<String> {
+    let res = match head(client, &resource.url, policy).await {
+        Ok(res) => res,
+        // Downloads fall back to the cache when the server is unreachable
+        Err(_) if cache::is_cached(cache_dir, &resource.url) => {
+            log::warn!(
+                "Couldn't reach {}, using the cached {}",
+                resource.url,
+                resource.what
+            );
+            return None;
+        }
+        Err(e) => {
+            return Some(format!(
+                "{} ({}) is unreachable: {}",
+                resource.what, resource.url, e
+            ))
+        }
+    };
+
+    if !res.status().is_success() {
+        return Some(format!(
+            "{} ({}) returned {}",
+            resource.what,
+            resource.url,
+            res.status()
+        ));
+    }
+
+    let header = |name| res.headers().get(name).and_then(|v| v.to_str().ok());
+    if header(CONTENT_LENGTH).and_then(|len| len.parse::<u64>().ok()) == Some(0) {
+        return Some(format!("{} ({}) is empty", resource.what, resource.url));
+    }
+
+    let content_type = header(CONTENT_TYPE).unwrap_or_default().to_lowercase();
+    let type_ok = content_type.is_empty()
+        || resource.content_types.is_empty()
+        || GENERIC_TYPES.iter().any(|t| content_type.starts_with(t))
+        || resource
+            .content_types
+            .iter()
+            .any(|t| content_type.starts_with(t));
+    if !type_ok {
+        return Some(format!(
+            "{} ({}) is served as {}, expected {}",
+            resource.what,
+            resource.url,
+            content_type,
+            resource.content_types.join(" or ")
+        ));
+    }
+
+    log::info!("Preflight check of {} passed", resource.url);
+    None
+}
+
+/// Makes sure every remote resource of the modpack is reachable and looks right
+///
+/// Also fetches and parses the packwiz `pack.toml`. All resources are checked at once and every
+/// problem is reported together, so the install can fail before it writes anything. Resources
+/// bundled in the offline payload are skipped, unreachable ones cached in `cache_dir` pass.
+pub async fn preflight(
+    config: &ModpackConfig,
+    policy: &DownloadPolicy,
+    cache_dir: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+    let payload = OfflinePayload::locate();
+    let bundled = |has: fn(&OfflinePayload) -> bool| payload.as_ref().is_some_and(has);
+
+    let mut resources = Vec::new();
+    let bundled_base_pack = bundled(OfflinePayload::has_base_pack);
+    if let Some(url) = config.base_pack().filter(|_| !bundled_base_pack) {
+        resources.push(Resource {
+            what: "Base pack",
+            url: url.to_string(),
+            content_types: &["application/zip", "application/x-zip"],
+        });
+    }
+    if !bundled(OfflinePayload::has_logo) {
+        resources.push(Resource {
+            what: "Logo",
+            url: config.logo_url.clone(),
+            content_types: &["image/"],
+        });
+    }
+
+    let client = Client::builder()
+        .connect_timeout(policy.stall_timeout)
+        .build()?;
+    let (results, pack) = join(
+        join_all(
+            resources
+                .iter()
+                .map(|r| check(&client, r, policy, cache_dir)),
+        ),
+        pack::fetch(&config.packwiz_url, policy),
+    )
+    .await;
+
+    let mut problems = results.into_iter().flatten().collect::<Vec<_>>();
+    match pack {
+        Ok(_) => {}
+        // Offline installs may only reach the packwiz server once they're on the right network,
+        // but without a base pack the instance can't be created without it
+        Err(e) if bundled_base_pack => log::warn!("{:#}", e),
+        Err(e) => problems.push(format!("{:#}", e)),
+    }
+
+    if !problems.is_empty() {
+        anyhow::bail!("{}", problems.join("\n"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,418 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::download::DownloadProgress;
+
+/// How often byte-level updates are forwarded to the sink
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Preflight,
+    DownloadLauncher,
+    ExtractLauncher,
+    InstallLauncher,
+    DownloadPack,
+    ExtractPack,
+    Configure,
+    DownloadIcon,
+    SyncMods,
+    Finalize,
+}
+
+impl Phase {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Phase::Preflight => "Check downloads",
+            Phase::DownloadLauncher => "Download PrismLauncher",
+            Phase::ExtractLauncher => "Extract PrismLauncher",
+            Phase::InstallLauncher => "Install PrismLauncher",
+            Phase::DownloadPack => "Download modpack",
+            Phase::ExtractPack => "Extract modpack",
+            Phase::Configure => "Configure instance",
+            Phase::DownloadIcon => "Download icon",
+            Phase::SyncMods => "Download mods",
+            Phase::Finalize => "Create shortcuts",
+        }
+    }
+}
+
+/// Phases of the modpack installation itself, with their share of the total work
+pub const MODPACK_PHASES: [(Phase, f32); 6] = [
+    (Phase::DownloadPack, 40.0),
+    (Phase::ExtractPack, 15.0),
+    (Phase::Configure, 5.0),
+    (Phase::DownloadIcon, 5.0),
+    (Phase::SyncMods, 40.0),
+    (Phase::Finalize, 5.0),
+];
+
+/// A single update of the currently running phase
+#[derive(Debug, Clone)]
+pub struct ProgressEvent {
+    pub phase: Phase,
+    /// Progress of the phase from 0.0 to 1.0
+    pub progress: f32,
+    pub message: String,
+    pub bytes: Option<DownloadProgress>,
+}
+
+impl ProgressEvent {
+    pub fn new(phase: Phase, progress: f32, message: impl Into<String>) -> Self {
+        Self {
+            phase,
+            progress,
+            message: message.into(),
+            bytes: None,
+        }
+    }
+
+    pub fn download(phase: Phase, bytes: DownloadProgress, message: impl Into<String>) -> Self {
+        let message = match bytes.retry {
+            Some((retry, retries)) => {
+                format!("Retrying ({}/{})... {}", retry, retries, message.into())
+            }
+            None => message.into(),
+        };
+
+        Self {
+            phase,
+            progress: bytes.fraction(),
+            message,
+            bytes: Some(bytes),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PhaseStatus {
+    pub phase: Phase,
+    pub label: &'static str,
+    pub weight: f32,
+    pub progress: f32,
+    pub done: bool,
+}
+
+/// Snapshot of the whole install, emitted to the UI as `install_progress`
+#[derive(Serialize, Debug, Clone)]
+pub struct InstallProgress {
+    pub phase: Phase,
+    pub message: String,
+    /// Progress of the whole install from 0.0 to 1.0
+    pub overall: f32,
+    pub phase_progress: f32,
+    pub bytes_done: Option<u64>,
+    pub bytes_total: Option<u64>,
+    /// Bytes per second
+    pub speed: Option<f64>,
+    /// Seconds left in the current phase
+    pub eta: Option<f64>,
+    pub phases: Vec<PhaseStatus>,
+}
+
+/// Receives progress updates of an install
+pub trait ProgressSink: Send + Sync {
+    fn report(&self, progress: &InstallProgress);
+}
+
+/// Turns per-phase events into weighted overall progress
+pub struct ProgressTracker<'a> {
+    sink: &'a dyn ProgressSink,
+    phases: Vec<PhaseStatus>,
+    current: usize,
+    phase_started: Instant,
+    last_report: Option<Instant>,
+    last_message: String,
+}
+
+impl<'a> ProgressTracker<'a> {
+    pub fn new(sink: &'a dyn ProgressSink, phases: &[(Phase, f32)]) -> Self {
+        let total = phases.iter().map(|(_, w)| w).sum::<f32>().max(f32::EPSILON);
+        let phases = phases
+            .iter()
+            .map(|(phase, weight)| PhaseStatus {
+                phase: *phase,
+                label: phase.label(),
+                weight: weight / total,
+                progress: 0.0,
+                done: false,
+            })
+            .collect();
+
+        Self {
+            sink,
+            phases,
+            current: 0,
+            phase_started: Instant::now(),
+            last_report: None,
+            last_message: String::new(),
+        }
+    }
+
+    fn overall(&self) -> f32 {
+        // Summing the weights may fall short of 1.0 by a rounding error
+        if self.phases.iter().all(|p| p.done) {
+            return 1.0;
+        }
+        self.phases
+            .iter()
+            .map(|p| p.weight * if p.done { 1.0 } else { p.progress })
+            .sum::<f32>()
+            .min(1.0)
+    }
+
+    pub fn update(&mut self, event: ProgressEvent) {
+        let index = match self.phases.iter().position(|p| p.phase == event.phase) {
+            Some(index) => index,
+            None => {
+                log::warn!("Progress for untracked phase {:?}", event.phase);
+                return;
+            }
+        };
+
+        if index < self.current {
+            log::debug!("Late progress for finished phase {:?}", event.phase);
+            return;
+        }
+
+        let phase_changed = index != self.current;
+        if phase_changed {
+            for phase in &mut self.phases[..index] {
+                phase.done = true;
+            }
+            self.current = index;
+            self.phase_started = Instant::now();
+        }
+
+        // Overall progress never goes back, e.g. when a download has to start over
+        let progress = event.progress.clamp(0.0, 1.0);
+        let status = &mut self.phases[index];
+        status.progress = status.progress.max(progress);
+        status.done |= progress >= 1.0;
+
+        // Chunk-level download updates are throttled, everything else goes straight through
+        let now = Instant::now();
+        let throttled = event.bytes.is_some()
+            && !phase_changed
+            && progress < 1.0
+            && event.message == self.last_message
+            && self
+                .last_report
+                .is_some_and(|last| now.duration_since(last) < REPORT_INTERVAL);
+        if throttled {
+            return;
+        }
+        self.last_report = Some(now);
+        self.last_message.clone_from(&event.message);
+
+        let elapsed = self.phase_started.elapsed().as_secs_f64();
+        let speed = event
+            .bytes
+            .filter(|_| elapsed > 0.0)
+            .map(|b| b.downloaded as f64 / elapsed);
+        let eta = event.bytes.zip(speed).and_then(|(b, speed)| {
+            let total = b.total?;
+            (speed > 0.0).then(|| total.saturating_sub(b.downloaded) as f64 / speed)
+        });
+
+        self.sink.report(&InstallProgress {
+            phase: event.phase,
+            message: event.message,
+            overall: self.overall(),
+            phase_progress: progress,
+            bytes_done: event.bytes.map(|b| b.downloaded),
+            bytes_total: event.bytes.and_then(|b| b.total),
+            speed,
+            eta,
+            phases: self.phases.clone(),
+        });
+    }
+}
+
+/// Forwards progress to the UI through the `install_progress` event
+pub struct TauriProgress {
+    app: AppHandle,
+}
+
+impl TauriProgress {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl ProgressSink for TauriProgress {
+    fn report(&self, progress: &InstallProgress) {
+        // A window that went away shouldn't take the install down with it
+        if let Err(e) = self.app.emit("install_progress", progress) {
+            log::warn!("Failed to emit install progress: {}", e);
+        }
+    }
+}
+
+/// Prints progress to stdout, used by the headless mode
+pub struct StdoutProgress;
+
+impl ProgressSink for StdoutProgress {
+    fn report(&self, progress: &InstallProgress) {
+        println!(
+            "[{:>5.1}%] {}: {}",
+            progress.overall * 100.0,
+            progress.phase.label(),
+            progress.message
+        );
+    }
+}
+
+/// Collects all progress updates, useful to inspect an install after it ran
+#[derive(Default)]
+pub struct MemoryProgress {
+    events: Mutex<Vec<InstallProgress>>,
+}
+
+impl MemoryProgress {
+    pub fn events(&self) -> Vec<InstallProgress> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl ProgressSink for MemoryProgress {
+    fn report(&self, progress: &InstallProgress) {
+        self.events.lock().unwrap().push(progress.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_progress;
+
+    fn bytes(downloaded: u64, total: u64) -> DownloadProgress {
+        DownloadProgress {
+            downloaded,
+            total: Some(total),
+            retry: None,
+        }
+    }
+
+    #[test]
+    fn weights_are_normalized() {
+        let sink = MemoryProgress::default();
+        let mut tracker = ProgressTracker::new(
+            &sink,
+            &[(Phase::Preflight, 1.0), (Phase::DownloadPack, 3.0)],
+        );
+        tracker.update(ProgressEvent::new(Phase::Preflight, 1.0, "checked"));
+        tracker.update(ProgressEvent::new(Phase::DownloadPack, 0.5, "half"));
+        tracker.update(ProgressEvent::new(Phase::DownloadPack, 1.0, "done"));
+
+        let overall = sink.events().iter().map(|e| e.overall).collect::<Vec<_>>();
+        assert_eq!(overall, [0.25, 0.625, 1.0]);
+    }
+
+    #[test]
+    fn skipped_phases_count_as_done() {
+        let sink = MemoryProgress::default();
+        let mut tracker = ProgressTracker::new(&sink, &MODPACK_PHASES);
+        tracker.update(ProgressEvent::new(Phase::Finalize, 0.0, "shortcuts"));
+
+        let event = sink.events().pop().unwrap();
+        let done = event.phases.iter().filter(|p| p.done).count();
+        assert_eq!(done, MODPACK_PHASES.len() - 1);
+        let expected = 1.0 - 5.0 / 110.0;
+        assert!((event.overall - expected).abs() < 1e-6, "{}", event.overall);
+    }
+
+    #[test]
+    fn overall_progress_never_goes_back() {
+        let sink = MemoryProgress::default();
+        let phases = [(Phase::DownloadPack, 1.0), (Phase::ExtractPack, 1.0)];
+        let mut tracker = ProgressTracker::new(&sink, &phases);
+        tracker.update(ProgressEvent::new(Phase::DownloadPack, 0.8, "downloading"));
+        tracker.update(ProgressEvent::new(
+            Phase::DownloadPack,
+            0.1,
+            "starting over",
+        ));
+        tracker.update(ProgressEvent::new(Phase::ExtractPack, 1.0, "extracted"));
+        tracker.update(ProgressEvent::new(Phase::DownloadPack, 0.0, "late update"));
+
+        let events = sink.events();
+        assert_eq!(events.len(), 3);
+        assert_progress(&events, &phases);
+        assert_eq!(events.last().unwrap().overall, 1.0);
+    }
+
+    #[test]
+    fn throttles_chunk_updates_only() {
+        let sink = MemoryProgress::default();
+        let mut tracker = ProgressTracker::new(&sink, &[(Phase::DownloadLauncher, 1.0)]);
+        for downloaded in 1..100 {
+            let event =
+                ProgressEvent::download(Phase::DownloadLauncher, bytes(downloaded, 100), "a");
+            tracker.update(event);
+        }
+        tracker.update(ProgressEvent::download(
+            Phase::DownloadLauncher,
+            bytes(50, 100),
+            "b",
+        ));
+        tracker.update(ProgressEvent::download(
+            Phase::DownloadLauncher,
+            bytes(100, 100),
+            "b",
+        ));
+
+        let events = sink.events();
+        let messages = events
+            .iter()
+            .map(|e| e.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, ["a", "b", "b"]);
+        assert_eq!(events[2].bytes_done, Some(100));
+        assert_eq!(events[2].bytes_total, Some(100));
+    }
+
+    #[test]
+    fn retries_are_announced() {
+        let progress = DownloadProgress {
+            retry: Some((2, 5)),
+            ..bytes(10, 100)
+        };
+        let event = ProgressEvent::download(Phase::DownloadPack, progress, "Downloading");
+        assert_eq!(event.message, "Retrying (2/5)... Downloading");
+        assert_eq!(event.progress, 0.1);
+    }
+
+    #[test]
+    fn ignores_untracked_phases() {
+        let sink = MemoryProgress::default();
+        let mut tracker = ProgressTracker::new(&sink, &MODPACK_PHASES);
+        tracker.update(ProgressEvent::new(Phase::DownloadLauncher, 0.5, "launcher"));
+        assert!(sink.events().is_empty());
+    }
+
+    #[test]
+    fn full_install_reaches_100_percent_in_order() {
+        let sink = MemoryProgress::default();
+        let phases = [
+            &[(Phase::Preflight, 2.0), (Phase::DownloadLauncher, 25.0)][..],
+            &MODPACK_PHASES[..],
+        ]
+        .concat();
+        let mut tracker = ProgressTracker::new(&sink, &phases);
+        for (phase, _) in &phases {
+            for progress in [0.0, 0.3, 0.7, 1.0] {
+                tracker.update(ProgressEvent::new(*phase, progress, phase.label()));
+            }
+        }
+
+        let events = sink.events();
+        assert_progress(&events, &phases);
+        assert_eq!(events.last().unwrap().overall, 1.0);
+        assert!(events.last().unwrap().phases.iter().all(|p| p.done));
+    }
+}
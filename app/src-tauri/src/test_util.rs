@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+
+use crate::deletion_guard::TemporaryFileCleaner;
+use crate::instance::{InstallManifest, MANIFEST_FILE};
+use crate::modpack::prelaunch_command;
+use crate::platform::UserPaths;
+use crate::progress::{InstallProgress, Phase};
+use crate::util::ModpackConfig;
+
+/// Serves a fixed set of files over HTTP on localhost for as long as the test runs
+///
+/// Responses carry an `ETag` and honor `If-None-Match`, every request is counted per path.
+pub struct Server {
+    pub url: String,
+    files: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    hits: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl Server {
+    pub fn new(files: &[(&str, &[u8])]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = Self {
+            url,
+            files: Arc::new(Mutex::new(HashMap::new())),
+            hits: Arc::new(Mutex::new(HashMap::new())),
+        };
+        for (path, body) in files {
+            server.set(path, body);
+        }
+
+        let (files, hits) = (server.files.clone(), server.hits.clone());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (files, hits) = (files.clone(), hits.clone());
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(&stream);
+                    let mut request = String::new();
+                    reader.read_line(&mut request).unwrap_or_default();
+                    let mut if_none_match = None;
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap_or_default() == 0 || line == "\r\n" {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("if-none-match") {
+                                if_none_match = Some(value.trim().to_string());
+                            }
+                        }
+                    }
+
+                    let mut parts = request.split_whitespace();
+                    let (method, path) = (
+                        parts.next().unwrap_or_default(),
+                        parts.next().unwrap_or_default(),
+                    );
+                    *hits.lock().unwrap().entry(path.to_string()).or_default() += 1;
+
+                    let body = files.lock().unwrap().get(path).cloned();
+                    let response = match body {
+                        Some(body) => {
+                            let etag = format!("\"{}\"", hex::encode(Sha256::digest(&body)));
+                            if if_none_match.as_deref() == Some(etag.as_str()) {
+                                format!("HTTP/1.1 304 Not Modified\r\nETag: {}\r\nConnection: close\r\n\r\n", etag).into_bytes()
+                            } else {
+                                let mut response = format!(
+                                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Type: application/octet-stream\r\nETag: {}\r\nConnection: close\r\n\r\n",
+                                    body.len(),
+                                    etag
+                                )
+                                .into_bytes();
+                                if method != "HEAD" {
+                                    response.extend_from_slice(&body);
+                                }
+                                response
+                            }
+                        }
+                        None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+                    };
+                    let _ = (&stream).write_all(&response);
+                });
+            }
+        });
+
+        server
+    }
+
+    /// Adds or replaces the file served at `path`
+    pub fn set(&self, path: &str, body: &[u8]) {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), body.to_vec());
+    }
+
+    /// How many requests `path` got so far
+    pub fn hits(&self, path: &str) -> usize {
+        self.hits
+            .lock()
+            .unwrap()
+            .get(path)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+}
+
+/// A modpack called `name` that syncs from `packwiz_url`
+pub fn config(name: &str, packwiz_url: &str) -> ModpackConfig {
+    ModpackConfig {
+        name: name.to_string(),
+        author: "Tests".to_string(),
+        description: String::new(),
+        logo_url: format!("{}/logo.png", packwiz_url.trim_end_matches("/pack.toml")),
+        packwiz_url: packwiz_url.to_string(),
+        base_pack_url: None,
+        theme: String::new(),
+        background: String::new(),
+        base_pack_sha256: None,
+        launcher_version: None,
+        launcher_fallback_versions: Vec::new(),
+        launcher_sha256: Default::default(),
+    }
+}
+
+/// Creates an instance `dir` with a manifest for the modpack `name`
+pub fn install(instances: &Path, dir: &str, name: &str, packwiz_url: &str) {
+    let dir = instances.join(dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let manifest = InstallManifest {
+        installer_version: "0.0.0".to_string(),
+        config: config(name, packwiz_url),
+        installed_at: 0,
+        updated_at: 0,
+        icon_key: uuid::Uuid::new_v4().to_string(),
+        shortcuts: Vec::new(),
+        files: Vec::new(),
+    };
+    std::fs::write(
+        dir.join(MANIFEST_FILE),
+        serde_json::to_vec(&manifest).unwrap(),
+    )
+    .unwrap();
+}
+
+/// Creates an instance `dir` the way installers before the manifest left it
+pub fn legacy_install(instances: &Path, dir: &str, packwiz_url: &str) {
+    let dir = instances.join(dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let cfg = format!(
+        "[General]\nPreLaunchCommand={}\niconKey=legacy\n",
+        prelaunch_command(packwiz_url)
+    );
+    std::fs::write(dir.join("instance.cfg"), cfg).unwrap();
+}
+
+/// Creates an empty directory that is removed again once the guard is dropped
+pub fn temp_dir() -> TemporaryFileCleaner {
+    let dir = TemporaryFileCleaner::new_with_extension("test");
+    std::fs::create_dir_all(dir.file_path()).unwrap();
+    dir
+}
+
+/// User directories below `root`, so installs never touch the real shortcuts or cache
+pub fn user_paths(root: &Path) -> UserPaths {
+    UserPaths {
+        home: Some(root.to_path_buf()),
+        data: Some(root.join(".local/share")),
+        desktop: Some(root.join("Desktop")),
+        cache: Some(root.join(".cache/downloads")),
+    }
+}
+
+/// Serves a packwiz pack at the root of `server`, with a metafile for each of `files`
+///
+/// The files themselves are served at `/files/<path>`.
+pub fn packwiz_pack(server: &Server, minecraft: &str, files: &[(&str, &[u8])]) {
+    let sha256 = |data: &[u8]| hex::encode(Sha256::digest(data));
+
+    let mut index = String::from("hash-format = \"sha256\"\n");
+    for (path, body) in files {
+        server.set(&format!("/files/{}", path), body);
+        let name = Path::new(path)
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let metafile = format!(
+            "name = \"{name}\"\nfilename = \"{name}\"\nside = \"both\"\n\n[download]\nurl = \"{url}\"\nhash-format = \"sha256\"\nhash = \"{hash}\"\n",
+            name = name,
+            url = server.url(&format!("/files/{}", path)),
+            hash = sha256(body),
+        );
+        let metafile_path = format!("{}.pw.toml", path.trim_end_matches(".jar"));
+        server.set(&format!("/{}", metafile_path), metafile.as_bytes());
+        index.push_str(&format!(
+            "\n[[files]]\nfile = \"{}\"\nhash = \"{}\"\nmetafile = true\n",
+            metafile_path,
+            sha256(metafile.as_bytes())
+        ));
+    }
+    server.set("/index.toml", index.as_bytes());
+
+    let pack = format!(
+        "name = \"Test Pack\"\nversion = \"1.0.0\"\npack-format = \"packwiz:1.1.0\"\n\n[index]\nfile = \"index.toml\"\nhash-format = \"sha256\"\nhash = \"{}\"\n\n[versions]\nminecraft = \"{}\"\nfabric = \"0.16.14\"\n",
+        sha256(index.as_bytes()),
+        minecraft
+    );
+    server.set("/pack.toml", pack.as_bytes());
+}
+
+/// Checks what every install reports: phases in the given order, overall progress only growing
+/// and never past 100%
+pub fn assert_progress(events: &[InstallProgress], phases: &[(Phase, f32)]) {
+    let order = |phase: Phase| phases.iter().position(|(p, _)| *p == phase).unwrap();
+    for event in events {
+        assert!((0.0..=1.0).contains(&event.overall), "{:?}", event);
+    }
+    for pair in events.windows(2) {
+        assert!(
+            order(pair[0].phase) <= order(pair[1].phase),
+            "{:?} reported after {:?}",
+            pair[1].phase,
+            pair[0].phase
+        );
+        assert!(
+            pair[0].overall <= pair[1].overall,
+            "progress went from {} back to {} at {:?}",
+            pair[0].overall,
+            pair[1].overall,
+            pair[1].message
+        );
+    }
+}
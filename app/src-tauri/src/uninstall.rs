@@ -0,0 +1,251 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Serialize;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::instance::{self, PreviousInstall};
+use crate::platform::{self, UserPaths};
+use crate::util::ModpackConfig;
+
+/// What an uninstall removed
+#[derive(Serialize, Debug, Default)]
+pub struct UninstallReport {
+    pub instances: Vec<PathBuf>,
+    pub files: Vec<PathBuf>,
+    /// Zip files the saves were backed up to
+    pub backups: Vec<PathBuf>,
+}
+
+/// Removes every instance of the modpack from `prism_data`, along with its icons and shortcuts
+///
+/// If `backup_dir` is set, the saves of each instance are zipped into it before anything is deleted.
+pub fn uninstall(
+    paths: &UserPaths,
+    prism_data: &Path,
+    config: &ModpackConfig,
+    backup_dir: Option<&Path>,
+) -> Result<UninstallReport, anyhow::Error> {
+    let installs = instance::find_installs(&prism_data.join("instances"), config);
+    if installs.is_empty() {
+        anyhow::bail!(
+            "{} is not installed in {}",
+            config.name,
+            prism_data.display()
+        );
+    }
+
+    let mut report = UninstallReport::default();
+    for install in installs {
+        log::info!("Uninstalling {}", install.dir.display());
+        if let Some(backup_dir) = backup_dir {
+            if let Some(backup) = backup_saves(&install.dir, backup_dir)? {
+                report.backups.push(backup);
+            }
+        }
+
+        for path in installed_files(paths, prism_data, config, &install) {
+            match std::fs::remove_file(&path) {
+                Ok(()) => {
+                    log::info!("Removed {}", path.display());
+                    report.files.push(path);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => log::warn!("Failed to remove {}: {}", path.display(), e),
+            }
+        }
+
+        std::fs::remove_dir_all(&install.dir)
+            .with_context(|| format!("Failed to remove {}", install.dir.display()))?;
+        report.instances.push(install.dir);
+    }
+
+    Ok(report)
+}
+
+/// Icons and shortcuts that belong to an install
+fn installed_files(
+    paths: &UserPaths,
+    prism_data: &Path,
+    config: &ModpackConfig,
+    install: &PreviousInstall,
+) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Some(icon_key) = &install.icon_key {
+        let icon = prism_data.join("icons").join(format!("{}.png", icon_key));
+        files.push(icon.with_extension("ico"));
+        files.push(icon);
+    }
+
+    match &install.manifest {
+        Some(manifest) => files.extend(manifest.shortcuts.iter().cloned()),
+        // Installs without a manifest only have the shortcuts the installer always creates. Other
+        // instances of a pack with the same name share their paths, so only the shortcuts
+        // launching this instance are its own.
+        None => {
+            let icon_key = install.icon_key.as_deref().unwrap_or_default();
+            let instance_name = install
+                .dir
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string());
+            files.extend(
+                platform::shortcut_paths(paths, &config.name, icon_key)
+                    .into_iter()
+                    .filter(|path| {
+                        platform::shortcut_target(path).is_none()
+                            || platform::shortcut_instance(path) == instance_name
+                    }),
+            );
+        }
+    }
+
+    files
+}
+
+/// Zips the saves of an instance into `backup_dir`, returns `None` if it has no saves
+fn backup_saves(instance_dir: &Path, backup_dir: &Path) -> Result<Option<PathBuf>, anyhow::Error> {
+    let saves = instance_dir.join("minecraft/saves");
+    let files = instance::list_files(&saves).unwrap_or_default();
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    std::fs::create_dir_all(backup_dir)?;
+    let name = instance_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "instance".to_string());
+    let backup = backup_dir.join(format!("{}-saves-{}.zip", name, instance::now()));
+
+    let mut zip = ZipWriter::new(File::create(&backup)?);
+    let options = SimpleFileOptions::default();
+    for file in files {
+        let entry = file
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        zip.start_file(entry, options)?;
+        std::io::copy(&mut File::open(saves.join(&file))?, &mut zip)?;
+    }
+    zip.finish()?;
+
+    log::info!(
+        "Backed up saves of {} to {}",
+        instance_dir.display(),
+        backup.display()
+    );
+    Ok(Some(backup))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, install, legacy_install};
+
+    const URL: &str = "https://example.com/pack/pack.toml";
+    const OTHER_URL: &str = "https://example.com/other/pack.toml";
+
+    #[test]
+    fn only_removes_instances_of_the_same_packwiz_url() {
+        let tmp = test_util::temp_dir();
+        let prism_data = tmp.file_path();
+        let paths = test_util::user_paths(prism_data);
+        let instances = prism_data.join("instances");
+        install(&instances, "Pack", "Pack", URL);
+        legacy_install(&instances, "Old Pack", URL);
+        install(&instances, "Pack (0)", "Pack", OTHER_URL);
+        legacy_install(&instances, "Pack (1)", OTHER_URL);
+        let proxied = format!("https://proxy.example.com/?url={}", URL);
+        legacy_install(&instances, "Pack (2)", &proxied);
+
+        let report = uninstall(&paths, prism_data, &test_util::config("Pack", URL), None).unwrap();
+        let mut removed = report.instances.clone();
+        removed.sort();
+        assert_eq!(
+            removed,
+            [instances.join("Old Pack"), instances.join("Pack")]
+        );
+        for kept in ["Pack (0)", "Pack (1)", "Pack (2)"] {
+            assert!(instances.join(kept).exists(), "{}", kept);
+        }
+    }
+
+    #[test]
+    fn refuses_when_nothing_matches_the_packwiz_url() {
+        let tmp = test_util::temp_dir();
+        let prism_data = tmp.file_path();
+        let paths = test_util::user_paths(prism_data);
+        let instances = prism_data.join("instances");
+        install(&instances, "Pack", "Pack", OTHER_URL);
+        legacy_install(&instances, "Pack (0)", OTHER_URL);
+
+        assert!(uninstall(&paths, prism_data, &test_util::config("Pack", URL), None).is_err());
+        assert!(instances.join("Pack").exists());
+        assert!(instances.join("Pack (0)").exists());
+    }
+
+    // Desktop entries are easy to write by hand, .lnk files aren't
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn keeps_legacy_shortcuts_of_other_instances() {
+        let tmp = test_util::temp_dir();
+        let prism_data = tmp.file_path();
+        let paths = test_util::user_paths(prism_data);
+        let instances = prism_data.join("instances");
+        legacy_install(&instances, "Pack", URL);
+
+        let applications = paths.data.as_ref().unwrap().join("applications");
+        let desktop = paths.desktop.as_ref().unwrap();
+        for (dir, instance) in [(&applications, "Pack"), (desktop, "Pack (1)")] {
+            std::fs::create_dir_all(dir).unwrap();
+            let entry = format!("[Desktop Entry]\nExec=prismlauncher -l \"{}\"\n", instance);
+            std::fs::write(dir.join("Pack.desktop"), entry).unwrap();
+        }
+
+        let report = uninstall(&paths, prism_data, &test_util::config("Pack", URL), None).unwrap();
+        assert_eq!(report.files, [applications.join("Pack.desktop")]);
+        assert!(desktop.join("Pack.desktop").exists());
+    }
+
+    #[test]
+    fn backs_up_saves_before_removing_the_instance() {
+        let tmp = test_util::temp_dir();
+        let prism_data = tmp.file_path();
+        let paths = test_util::user_paths(prism_data);
+        let instances = prism_data.join("instances");
+        install(&instances, "Pack", "Pack", URL);
+        let world = instances.join("Pack/minecraft/saves/World");
+        std::fs::create_dir_all(world.join("region")).unwrap();
+        std::fs::write(world.join("level.dat"), b"level").unwrap();
+        std::fs::write(world.join("region/r.0.0.mca"), b"region").unwrap();
+
+        // A file where the backup directory should be makes the backup fail
+        let blocked = prism_data.join("blocked");
+        std::fs::write(&blocked, b"").unwrap();
+        let config = test_util::config("Pack", URL);
+        assert!(uninstall(&paths, prism_data, &config, Some(&blocked)).is_err());
+        assert!(world.join("level.dat").exists());
+
+        let backups = prism_data.join("backups");
+        let report = uninstall(&paths, prism_data, &config, Some(&backups)).unwrap();
+        assert_eq!(report.instances, [instances.join("Pack")]);
+        assert!(!instances.join("Pack").exists());
+
+        let [backup] = report.backups.as_slice() else {
+            panic!("expected one backup, got {:?}", report.backups);
+        };
+        assert!(backup.starts_with(&backups));
+        let mut zip = zip::ZipArchive::new(File::open(backup).unwrap()).unwrap();
+        let mut names = zip.file_names().map(str::to_string).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["World/level.dat", "World/region/r.0.0.mca"]);
+        let mut level = String::new();
+        std::io::Read::read_to_string(&mut zip.by_name("World/level.dat").unwrap(), &mut level)
+            .unwrap();
+        assert_eq!(level, "level");
+    }
+}
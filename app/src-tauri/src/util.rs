@@ -1,87 +1,83 @@
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::metadata::{self, MetadataError};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ModpackConfig {
     pub name: String,
     pub author: String,
     pub description: String,
     pub logo_url: String,
     pub packwiz_url: String,
-    pub base_pack_url: String,
+    /// Zip with the instance files, the instance is generated from `pack.toml` if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_pack_url: Option<String>,
     pub theme: String,
     pub background: String,
+    /// Expected sha256 of the file at `base_pack_url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_pack_sha256: Option<String>,
+    /// Semver requirement for the PrismLauncher release to download, the newest release if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launcher_version: Option<String>,
+    /// Requirements tried in order when the selected release has no asset for this system
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub launcher_fallback_versions: Vec<String>,
+    /// Pinned sha256 of PrismLauncher release assets, keyed by asset file name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub launcher_sha256: HashMap<String, String>,
+}
+
+impl ModpackConfig {
+    /// `base_pack_url`, unless it's unset or blank
+    pub fn base_pack(&self) -> Option<&str> {
+        self.base_pack_url
+            .as_deref()
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+    }
 }
 
-/// Reads the URL that was appended to the end of the executable
-pub fn read_metadata() -> Result<ModpackConfig, String> {
+/// Reads the modpack config that was appended to the end of the executable
+pub fn read_metadata() -> Result<ModpackConfig, MetadataError> {
     if cfg!(debug_assertions) {
         return Ok(ModpackConfig {
             name: "Minecolonies".to_string(),
             author: "sshcrack".to_string(),
             description: "A modpack focused on building and managing colonies with the Minecolonies mod. Includes various quality of life mods and performance improvements.".to_string(),
             logo_url: "https://discord.do/wp-content/uploads/2023/08/MineColonies.jpg".to_string(),
-            base_pack_url: "http://localhost:3001/base_modpack.zip".to_string(),
+            base_pack_url: Some("http://localhost:3001/base_modpack.zip".to_string()),
             packwiz_url: "http://localhost:3000".to_string(),
             theme: "dark".to_string(),
-            background: "deepslate".to_string()
+            background: "deepslate".to_string(),
+            base_pack_sha256: None,
+            launcher_version: None,
+            launcher_fallback_versions: Vec::new(),
+            launcher_sha256: HashMap::new(),
         });
     }
 
     // Get the path to the current executable
-    let exe_path =
-        env::current_exe().map_err(|e| format!("Failed to get executable path: {}", e))?;
+    let exe_path = env::current_exe()?;
+    let mut file = File::open(exe_path)?;
 
-    let mut file = File::open(exe_path).map_err(|e| format!("Failed to open executable: {}", e))?;
-
-    // Get the file size
-    let file_size = file
-        .metadata()
-        .map_err(|e| format!("Failed to read file metadata: {}", e))?
-        .len();
-
-    // First read the last 8 bytes which contain the URL length
-    if file_size < 8 {
-        return Err("Executable file is too small".to_string());
+    let trailer = metadata::find_trailer(&mut file)?;
+    if trailer.version == 0 {
+        log::warn!("Installer uses the legacy metadata layout without a checksum");
     }
 
-    // Move to position where URL length is stored (last 8 bytes)
-    file.seek(SeekFrom::End(-8))
-        .map_err(|e| format!("Failed to seek to URL length: {}", e))?;
-
-    // Read the URL length (u64, 8 bytes)
-    let mut length_bytes = [0u8; 8];
-    file.read_exact(&mut length_bytes)
-        .map_err(|e| format!("Failed to read URL length: {}", e))?;
-
-    // Convert bytes to u64
-    let url_length = u64::from_le_bytes(length_bytes);
-
-    // Validate URL length
-    if url_length == 0 || url_length > file_size - 8 {
-        return Err(format!("Invalid URL length: {}", url_length));
-    }
-
-    // Move to position where URL starts
-    file.seek(SeekFrom::End(-8 - (url_length as i64)))
-        .map_err(|e| format!("Failed to seek to URL: {}", e))?;
-
-    let url_length =
-        usize::try_from(url_length).map_err(|e| format!("Failed to convert URL length: {}", e))?;
-
-    // Read the URL
-    let mut url_bytes = vec![0u8; url_length];
-    file.read_exact(&mut url_bytes)
-        .map_err(|e| format!("Failed to read URL: {}", e))?;
-
-    // Convert bytes to String
-    let raw_str =
-        String::from_utf8(url_bytes).map_err(|e| format!("URL is not valid UTF-8: {}", e))?;
+    serde_json::from_slice(&trailer.payload)
+        .map_err(|e| MetadataError::Corrupted(format!("invalid modpack config: {}", e)))
+}
 
-    serde_json::from_str(&raw_str)
-        .map_err(|e| format!("Failed to parse URL as JSON: {}", e))
-        .map(|config: ModpackConfig| config)
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
 }
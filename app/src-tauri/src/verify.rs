@@ -0,0 +1,305 @@
+use std::path::{Path, PathBuf};
+
+use configparser::ini::{Ini, WriteOptions};
+use futures_util::{pin_mut, StreamExt};
+use image::ImageReader;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::cache;
+use crate::deletion_guard::TemporaryFileCleaner;
+use crate::download::DownloadPolicy;
+use crate::instance::{InstallManifest, PreviousInstall, MANIFEST_FILE};
+use crate::modpack::{prelaunch_command, PACKWIZ_BOOTSTRAP};
+use crate::offline::OfflinePayload;
+use crate::platform::{self, UserPaths};
+use crate::util::ModpackConfig;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    MissingBootstrap,
+    ModifiedBootstrap,
+    PreLaunchCommand,
+    OverrideCommands,
+    MissingIcon,
+    MissingShortcut,
+    BrokenShortcut,
+}
+
+impl IssueKind {
+    pub const ALL: [IssueKind; 7] = [
+        IssueKind::MissingBootstrap,
+        IssueKind::ModifiedBootstrap,
+        IssueKind::PreLaunchCommand,
+        IssueKind::OverrideCommands,
+        IssueKind::MissingIcon,
+        IssueKind::MissingShortcut,
+        IssueKind::BrokenShortcut,
+    ];
+}
+
+/// Something about an installed instance that differs from what the installer set up
+#[derive(Serialize, Debug, Clone)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub message: String,
+    pub path: Option<PathBuf>,
+}
+
+impl Issue {
+    fn new(kind: IssueKind, message: impl Into<String>, path: &Path) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            path: Some(path.to_path_buf()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct InstanceReport {
+    pub instance: PathBuf,
+    pub issues: Vec<Issue>,
+}
+
+fn icon_key(install: &PreviousInstall) -> Option<String> {
+    install.icon_key.clone().or_else(|| {
+        let mut cfg = Ini::new_cs();
+        cfg.load(install.dir.join("instance.cfg")).ok()?;
+        cfg.get("General", "iconKey")
+    })
+}
+
+fn shortcuts(paths: &UserPaths, install: &PreviousInstall, config: &ModpackConfig) -> Vec<PathBuf> {
+    match &install.manifest {
+        Some(manifest) => manifest.shortcuts.clone(),
+        None => {
+            platform::shortcut_paths(paths, &config.name, &icon_key(install).unwrap_or_default())
+        }
+    }
+}
+
+/// Checks an installed instance against what `install_modpack` set up
+pub fn verify(
+    paths: &UserPaths,
+    prism_data: &Path,
+    install: &PreviousInstall,
+    config: &ModpackConfig,
+) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let jar = install.dir.join("minecraft/packwiz_bootstrap.jar");
+    match std::fs::read(&jar) {
+        Ok(content) if content != PACKWIZ_BOOTSTRAP => issues.push(Issue::new(
+            IssueKind::ModifiedBootstrap,
+            "packwiz_bootstrap.jar doesn't match the installer's version",
+            &jar,
+        )),
+        Ok(_) => {}
+        Err(_) => issues.push(Issue::new(
+            IssueKind::MissingBootstrap,
+            "packwiz_bootstrap.jar is missing",
+            &jar,
+        )),
+    }
+
+    let cfg_path = install.dir.join("instance.cfg");
+    let mut cfg = Ini::new_cs();
+    let _ = cfg.load(&cfg_path);
+
+    let expected = prelaunch_command(&config.packwiz_url);
+    match cfg.get("General", "PreLaunchCommand") {
+        Some(cmd) if cmd == expected => {}
+        found => issues.push(Issue::new(
+            IssueKind::PreLaunchCommand,
+            format!(
+                "Pre-launch command is '{}' instead of '{}'",
+                found.unwrap_or_default(),
+                expected
+            ),
+            &cfg_path,
+        )),
+    }
+
+    if cfg.get("General", "OverrideCommands").as_deref() != Some("true") {
+        issues.push(Issue::new(
+            IssueKind::OverrideCommands,
+            "Custom commands are disabled, so packwiz won't run",
+            &cfg_path,
+        ));
+    }
+
+    match icon_key(install) {
+        Some(key) => {
+            let icon = prism_data.join("icons").join(format!("{}.png", key));
+            if !icon.exists() {
+                issues.push(Issue::new(IssueKind::MissingIcon, "Icon is missing", &icon));
+            }
+        }
+        None => issues.push(Issue {
+            kind: IssueKind::MissingIcon,
+            message: "Instance has no icon".to_string(),
+            path: None,
+        }),
+    }
+
+    for shortcut in shortcuts(paths, install, config) {
+        if !shortcut.exists() {
+            issues.push(Issue::new(
+                IssueKind::MissingShortcut,
+                "Shortcut is missing",
+                &shortcut,
+            ));
+            continue;
+        }
+
+        if let Some(target) = platform::shortcut_target(&shortcut) {
+            if !target.exists() {
+                issues.push(Issue::new(
+                    IssueKind::BrokenShortcut,
+                    format!(
+                        "Shortcut points to {}, which doesn't exist",
+                        target.display()
+                    ),
+                    &shortcut,
+                ));
+            }
+        }
+    }
+
+    issues
+}
+
+/// Fetches the modpack logo and saves it as png and ico next to each other
+async fn restore_icon(
+    cache_dir: Option<&Path>,
+    logo_url: &str,
+    icon_path: &Path,
+) -> Result<(), anyhow::Error> {
+    let tmp = TemporaryFileCleaner::new();
+    let bundled = OfflinePayload::locate().and_then(|p| p.extract_logo(tmp.file_path()));
+    match bundled {
+        Some(res) => {
+            res?;
+        }
+        None => {
+            let download = cache::fetch(
+                cache_dir,
+                logo_url,
+                tmp.file_path(),
+                &DownloadPolicy::default(),
+                None,
+            );
+            pin_mut!(download);
+            while let Some(res) = download.next().await {
+                res?;
+            }
+        }
+    }
+
+    let icon = ImageReader::open(tmp.file_path())?
+        .with_guessed_format()?
+        .decode()?;
+    std::fs::create_dir_all(icon_path.parent().unwrap())?;
+    icon.save(icon_path)?;
+    icon.save(icon_path.with_extension("ico"))?;
+
+    Ok(())
+}
+
+/// Fixes the given kinds of issues, returns the issues that are left afterwards
+pub async fn repair(
+    paths: &UserPaths,
+    prism_data: &Path,
+    install: &PreviousInstall,
+    config: &ModpackConfig,
+    prism_exec: Option<&Path>,
+    kinds: &[IssueKind],
+) -> Result<Vec<Issue>, anyhow::Error> {
+    let issues = verify(paths, prism_data, install, config);
+    let fix = |kind: IssueKind| {
+        issues
+            .iter()
+            .any(|i| i.kind == kind && kinds.contains(&kind))
+    };
+    let mut manifest = InstallManifest::read(&install.dir);
+
+    if fix(IssueKind::MissingBootstrap) || fix(IssueKind::ModifiedBootstrap) {
+        let mc_folder = install.dir.join("minecraft");
+        std::fs::create_dir_all(&mc_folder)?;
+        std::fs::write(mc_folder.join("packwiz_bootstrap.jar"), PACKWIZ_BOOTSTRAP)?;
+        log::info!("Restored packwiz bootstrap in {}", mc_folder.display());
+    }
+
+    let mut icon_key = icon_key(install);
+    if fix(IssueKind::MissingIcon) {
+        let key = icon_key.get_or_insert_with(|| Uuid::new_v4().to_string());
+        let icon = prism_data.join("icons").join(format!("{}.png", key));
+        restore_icon(paths.cache.as_deref(), &config.logo_url, &icon).await?;
+        log::info!("Restored icon {}", icon.display());
+
+        if let Some(manifest) = &mut manifest {
+            manifest.icon_key = key.clone();
+        }
+    }
+
+    if fix(IssueKind::PreLaunchCommand)
+        || fix(IssueKind::OverrideCommands)
+        || fix(IssueKind::MissingIcon)
+    {
+        let cfg_path = install.dir.join("instance.cfg");
+        let mut cfg = Ini::new_cs();
+        let _ = cfg.load(&cfg_path);
+
+        if fix(IssueKind::PreLaunchCommand) {
+            let cmd = prelaunch_command(&config.packwiz_url);
+            cfg.set("General", "PreLaunchCommand", Some(cmd));
+        }
+        if fix(IssueKind::OverrideCommands) {
+            cfg.set("General", "OverrideCommands", Some("true".to_string()));
+        }
+        if fix(IssueKind::MissingIcon) {
+            cfg.set("General", "iconKey", icon_key.clone());
+        }
+
+        let write_options = WriteOptions::new_with_params(true, 2, 1);
+        cfg.pretty_write(&cfg_path, &write_options)?;
+        log::info!("Restored instance settings in {}", cfg_path.display());
+    }
+
+    if fix(IssueKind::MissingShortcut) || fix(IssueKind::BrokenShortcut) {
+        let prism_exec = prism_exec.ok_or_else(|| {
+            anyhow::anyhow!("PrismLauncher executable not found, can't recreate shortcuts")
+        })?;
+        let instance_name = install
+            .dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow::anyhow!("Invalid instance directory"))?;
+        let icon = prism_data
+            .join("icons")
+            .join(format!("{}.ico", icon_key.clone().unwrap_or_default()));
+
+        let created =
+            platform::create_shortcut(paths, prism_exec, &instance_name, &config.name, &icon)
+                .await?;
+        if let Some(manifest) = &mut manifest {
+            manifest.shortcuts = created;
+        }
+    }
+
+    if let Some(manifest) = &manifest {
+        std::fs::write(
+            install.dir.join(MANIFEST_FILE),
+            serde_json::to_vec_pretty(manifest)?,
+        )?;
+    }
+
+    let install = PreviousInstall {
+        dir: install.dir.clone(),
+        icon_key,
+        manifest,
+    };
+    Ok(verify(paths, prism_data, &install, config))
+}
@@ -1,7 +1,7 @@
 import { useContext, useMemo, useEffect } from "react";
 import Titlebar from "./components/Titlebar";
 import { ModpackConfigContext } from './components/ModpackConfigProvider';
-import { ModpackInfo, InstallOptions, InstallProgress } from "./components/installation";
+import { ModpackInfo, InstallOptions, InstallProgress, VerifyInstance } from "./components/installation";
 import { useInstallation } from "./hooks";
 import { getCardStyle } from "./styles/MinecraftUI";
 import ImportDialog from './components/ImportDialog';
@@ -16,8 +16,10 @@ function App() {
     installing,
     progress,
     progressMessage,
+    progressDetails,
     hasLauncher,
     startInstallation,
+    cancelInstallation,
     error,
     retryInstallation,
     resetInstallation,
@@ -60,23 +62,32 @@ function App() {
           {/* Modpack Information Section */}
           <ModpackInfo cardStyle={cardStyle} />          {/* Installation Options or Progress Section */}
           {!installing ? (
-            <InstallOptions
-              cardStyle={cardStyle}
-              installType={installType}
-              setInstallType={setInstallType}
-              installPath={installPath}
-              setInstallPath={setInstallPath}
-              startInstallation={startInstallation}
-              hasLauncher={hasLauncher}
-            />
+            <>
+              <InstallOptions
+                cardStyle={cardStyle}
+                installType={installType}
+                setInstallType={setInstallType}
+                installPath={installPath}
+                setInstallPath={setInstallPath}
+                startInstallation={startInstallation}
+                hasLauncher={hasLauncher}
+              />
+              {/* A portable install is verified in its directory, otherwise the detected PrismLauncher */}
+              <VerifyInstance
+                cardStyle={cardStyle}
+                dataDir={installType === "portable" && installPath ? installPath : null}
+              />
+            </>
           ) : (
             <InstallProgress
               cardStyle={cardStyle}
               progress={progress}
               progressMessage={progressMessage}
+              progressDetails={progressDetails}
               error={error}
               onRetry={retryInstallation}
               onCancel={resetInstallation}
+              onAbort={cancelInstallation}
             />
           )}
         </div>
@@ -7,13 +7,17 @@ export type ModpackConfigState = {
     description: string,
     logo_url: string,
     packwiz_url: string,
+    // Read from the pack's pack.toml, missing if it couldn't be fetched
+    pack_name?: string,
+    version?: string,
     minecraft?: string,
     modloader?: {
         type: string,
         version: string
     },
     theme: "dark" | "light",
-    background: string
+    background: string,
+    pack_error?: string
 }
 
 export const ModpackConfigContext = React.createContext<ModpackConfigState>({} as any)
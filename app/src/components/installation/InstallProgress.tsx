@@ -1,22 +1,47 @@
 import { CSSProperties } from "react";
 import { Card, CardHeader, CardBody, Progress, Button } from '@heroui/react';
+import { InstallProgressEvent } from "../../types";
 
 interface InstallProgressProps {
     cardStyle: CSSProperties;
     progress: number;
     progressMessage: string;
+    progressDetails?: InstallProgressEvent | null;
     error?: string | null;
     onRetry?: () => void;
     onCancel?: () => void;
+    onAbort?: () => void;
+}
+
+function formatBytes(bytes: number) {
+    const units = ["B", "KB", "MB", "GB"];
+    let value = bytes;
+    let unit = 0;
+    while (value >= 1024 && unit < units.length - 1) {
+        value /= 1024;
+        unit++;
+    }
+
+    return `${value.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`;
+}
+
+function formatEta(seconds: number) {
+    if (seconds < 60) {
+        return `${Math.ceil(seconds)}s`;
+    }
+
+    return `${Math.floor(seconds / 60)}m ${Math.ceil(seconds % 60)}s`;
 }
 
 export default function InstallProgress({
     cardStyle,
     progress,
     progressMessage,
+    progressDetails,
     error,
     onRetry,
-    onCancel
+    onCancel,
+    onAbort
 }: InstallProgressProps) {
     const isError = !!error;
 
@@ -40,6 +65,15 @@ export default function InstallProgress({
                     />
                 )}
 
+                {!isError && progressDetails && progressDetails.bytes_done !== null && (
+                    <p className="text-center text-xs text-gray-500 dark:text-gray-400 mb-2">
+                        {formatBytes(progressDetails.bytes_done)}
+                        {progressDetails.bytes_total !== null && ` / ${formatBytes(progressDetails.bytes_total)}`}
+                        {progressDetails.speed !== null && ` · ${formatBytes(progressDetails.speed)}/s`}
+                        {progressDetails.eta !== null && ` · ${formatEta(progressDetails.eta)} left`}
+                    </p>
+                )}
+
                 {isError ? (
                     <div className="text-center">
                         <div className="p-4 mb-4 bg-red-100 dark:bg-red-900/30 border border-red-200 dark:border-red-800 rounded-md">
@@ -81,6 +115,34 @@ export default function InstallProgress({
                             : "Installation complete!")}
                     </p>
                 )}
+
+                {!isError && progressDetails && (
+                    <ul className="mt-4 space-y-1 text-sm">
+                        {progressDetails.phases.map((phase) => (
+                            <li key={phase.phase} className="flex items-center gap-2 text-gray-700 dark:text-gray-300">
+                                <span className={phase.done ? "text-green-500 dark:text-green-400" : "text-gray-400"}>
+                                    {phase.done ? "✔" : (phase.phase === progressDetails.phase ? "➜" : "•")}
+                                </span>
+                                <span className={phase.phase === progressDetails.phase && !phase.done ? "font-medium" : ""}>
+                                    {phase.label}
+                                </span>
+                            </li>
+                        ))}
+                    </ul>
+                )}
+
+                {!isError && progress < 1 && onAbort && (
+                    <div className="flex justify-center mt-4">
+                        <Button
+                            color="default"
+                            variant="flat"
+                            onPress={onAbort}
+                            className="font-minecraft uppercase tracking-wide text-xs"
+                        >
+                            Cancel
+                        </Button>
+                    </div>
+                )}
             </CardBody>
         </Card>
     );
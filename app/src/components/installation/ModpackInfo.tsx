@@ -7,7 +7,7 @@ interface ModpackInfoProps {
 }
 
 export default function ModpackInfo({ cardStyle }: ModpackInfoProps) {
-    const { name, description, author, logo_url, minecraft, modloader } = useContext(ModpackConfigContext);
+    const { name, description, author, logo_url, version, minecraft, modloader, pack_error } = useContext(ModpackConfigContext);
 
     return (
         <Card style={cardStyle} className="mb-4 overflow-hidden">
@@ -36,6 +36,11 @@ export default function ModpackInfo({ cardStyle }: ModpackInfoProps) {
 
                     {/* Minecraft and modloader info */}
                     <div className="flex flex-wrap gap-2 mt-2">
+                        {version && (
+                            <span className="px-2 py-1 bg-blue-100 dark:bg-blue-800/30 text-blue-700 dark:text-blue-300 rounded text-xs font-medium">
+                                Version {version}
+                            </span>
+                        )}
                         {minecraft && (
                             <span className="px-2 py-1 bg-green-100 dark:bg-green-800/30 text-green-700 dark:text-green-300 rounded text-xs font-medium">
                                 Minecraft {minecraft}
@@ -55,6 +60,11 @@ export default function ModpackInfo({ cardStyle }: ModpackInfoProps) {
                         {description}
                     </p>
                 )}
+                {pack_error && (
+                    <p className="text-xs text-yellow-700 dark:text-yellow-400" title={pack_error}>
+                        Couldn't load the pack details, the modpack server may be unreachable.
+                    </p>
+                )}
             </CardBody>
         </Card>
     );
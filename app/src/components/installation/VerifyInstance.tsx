@@ -0,0 +1,111 @@
+import { CSSProperties } from "react";
+import { Card, CardHeader, CardBody, Button } from '@heroui/react';
+import { useVerification } from "../../hooks";
+
+interface VerifyInstanceProps {
+    cardStyle: CSSProperties;
+    dataDir: string | null;
+}
+
+export default function VerifyInstance({
+    cardStyle,
+    dataDir
+}: VerifyInstanceProps) {
+    const {
+        reports,
+        selected,
+        checking,
+        repairing,
+        error,
+        verify,
+        toggleKind,
+        repair
+    } = useVerification(dataDir);
+
+    return (
+        <Card style={cardStyle} className="mt-4 border-2 border-gray-200 dark:border-gray-700 overflow-hidden">
+            <CardHeader className="pb-0 pt-6 px-6 flex justify-between border-b border-gray-200 dark:border-gray-700">
+                <h2 className="text-sm font-bold text-gray-800 dark:text-gray-200 font-minecraft">Verify Installation</h2>
+                <Button
+                    size="sm"
+                    color="primary"
+                    variant="ghost"
+                    isLoading={checking}
+                    isDisabled={repairing !== null}
+                    onPress={verify}
+                    className="font-minecraft uppercase tracking-wide text-xs mb-2"
+                >
+                    {reports ? "Check Again" : "Check"}
+                </Button>
+            </CardHeader>
+            <CardBody className="p-6">
+                {error && (
+                    <div className="p-4 mb-4 bg-red-100 dark:bg-red-900/30 border border-red-200 dark:border-red-800 rounded-md">
+                        <p className="text-red-600 dark:text-red-400 text-sm font-mono overflow-auto max-h-32 whitespace-pre-wrap">
+                            {error}
+                        </p>
+                    </div>
+                )}
+
+                {!reports && (
+                    <p className="text-sm text-gray-600 dark:text-gray-400">
+                        Check installed instances of the modpack for broken settings, files and shortcuts
+                    </p>
+                )}
+
+                {reports && reports.length === 0 && (
+                    <p className="text-sm text-gray-600 dark:text-gray-400">The modpack isn't installed yet</p>
+                )}
+
+                {reports?.map((report) => {
+                    const kinds = selected[report.instance] ?? [];
+                    return (
+                        <div key={report.instance} className="mb-4 last:mb-0">
+                            <p className="font-medium text-gray-800 dark:text-gray-200 break-all">{report.instance}</p>
+                            {report.issues.length === 0 ? (
+                                <p className="text-sm text-green-500 dark:text-green-400">No issues found</p>
+                            ) : (
+                                <>
+                                    <ul className="mt-2 space-y-1 text-sm">
+                                        {report.issues.map((issue, i) => {
+                                            const id = `${report.instance}-${i}`;
+                                            return (
+                                                <li key={id} className="flex items-start">
+                                                    <input
+                                                        type="checkbox"
+                                                        id={id}
+                                                        checked={kinds.includes(issue.kind)}
+                                                        onChange={() => toggleKind(report.instance, issue.kind)}
+                                                        disabled={repairing !== null}
+                                                        className="mt-1 mr-2 accent-blue-600 dark:accent-blue-400"
+                                                    />
+                                                    <label htmlFor={id} className="cursor-pointer text-gray-700 dark:text-gray-300">
+                                                        {issue.message}
+                                                        {issue.path && (
+                                                            <span className="block text-xs text-gray-500 dark:text-gray-400 break-all">{issue.path}</span>
+                                                        )}
+                                                    </label>
+                                                </li>
+                                            );
+                                        })}
+                                    </ul>
+                                    <Button
+                                        size="sm"
+                                        color="primary"
+                                        variant="solid"
+                                        isLoading={repairing === report.instance}
+                                        isDisabled={kinds.length === 0 || checking || (repairing !== null && repairing !== report.instance)}
+                                        onPress={() => repair(report.instance)}
+                                        className="mt-2 font-minecraft text-white uppercase tracking-wide shadow-md text-xs"
+                                    >
+                                        Repair Selected
+                                    </Button>
+                                </>
+                            )}
+                        </div>
+                    );
+                })}
+            </CardBody>
+        </Card>
+    );
+}
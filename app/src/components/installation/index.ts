@@ -1,3 +1,4 @@
 export { default as InstallOptions } from './InstallOptions';
 export { default as InstallProgress } from './InstallProgress';
-export { default as ModpackInfo } from './ModpackInfo';
\ No newline at end of file
+export { default as ModpackInfo } from './ModpackInfo';
+export { default as VerifyInstance } from './VerifyInstance';
\ No newline at end of file
@@ -1 +1,2 @@
-export * from './useInstallation';
\ No newline at end of file
+export * from './useInstallation';
+export * from './useVerification';
\ No newline at end of file
@@ -1,6 +1,6 @@
-import { useState, useEffect, useMemo } from "react";
+import { useState, useEffect, useMemo, useRef } from "react";
 import { listen } from "@tauri-apps/api/event";
-import { InstallType } from "../types";
+import { InstallProgressEvent, InstallType } from "../types";
 import { invoke } from '@tauri-apps/api/core';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import { platform } from '@tauri-apps/plugin-os';
@@ -11,11 +11,13 @@ export function useInstallation() {
     const [installing, setInstalling] = useState(false);
     const [progress, setProgress] = useState(0);
     const [progressMessage, setProgressMessage] = useState("");
+    const [progressDetails, setProgressDetails] = useState<InstallProgressEvent | null>(null);
     const [hasLauncher, setHasLauncher] = useState<boolean | null>(null);
     const [error, setError] = useState<string | null>(null);
     const [showImportDialog, setShowImportDialog] = useState(false);
     const [isNewInstallation, setIsNewInstallation] = useState(false);
     const isWindows = useMemo(() => platform() === "windows", [])
+    const cancelled = useRef(false);
 
     // Check if PrismLauncher is already installed
     useEffect(() => {
@@ -40,26 +42,41 @@ export function useInstallation() {
         setInstalling(false);
         setProgress(0);
         setProgressMessage("");
+        setProgressDetails(null);
         setError(null);
     };
 
+    // Abort the running installation, the backend rolls back what it created so far
+    const cancelInstallation = async () => {
+        cancelled.current = true;
+        setProgressMessage("Cancelling installation...");
+        try {
+            await invoke<boolean>("cancel_install");
+        } catch (error) {
+            console.error("Failed to cancel installation:", error);
+        }
+    };
+
     // Retry the installation after an error
     const retryInstallation = () => {
         setError(null);
         startInstallation();
     };    // Installation function using Tauri commands
     const startInstallation = async () => {
+        cancelled.current = false;
         setInstalling(true);
         setProgress(0);
         setProgressMessage("Starting installation...");
+        setProgressDetails(null);
         setError(null);
         try {
             // Setup event listeners for progress updates
-            const unlistenProgress = await listen<[number, string]>("install_progress", (event) => {
-                const [percentage, message] = event.payload;
-                setProgress(percentage);
+            const unlistenProgress = await listen<InstallProgressEvent>("install_progress", (event) => {
+                const { overall, message } = event.payload;
+                setProgress(overall);
                 setProgressMessage(message);
-                console.log(`Progress: ${percentage}%, Message: ${message}`);
+                setProgressDetails(event.payload);
+                console.log(`Progress: ${overall}%, Message: ${message}`);
             });
 
             const unlistenImportDialog = await listen("import_dialog", () => {
@@ -88,6 +105,12 @@ export function useInstallation() {
                 await getCurrentWindow().close();
             }, 2000);
         } catch (error) {
+            if (cancelled.current) {
+                console.log("Installation cancelled");
+                resetInstallation();
+                return;
+            }
+
             console.error("Installation failed:", error);
             // Set error state instead of just updating progress message
             setError(`${error}`);
@@ -101,8 +124,10 @@ export function useInstallation() {
         installing,
         progress,
         progressMessage,
+        progressDetails,
         hasLauncher,
         startInstallation,
+        cancelInstallation,
         error,
         retryInstallation,
         resetInstallation,
@@ -0,0 +1,82 @@
+import { useState, useEffect } from "react";
+import { invoke } from '@tauri-apps/api/core';
+import { InstanceReport, IssueKind } from "../types";
+
+export function useVerification(dataDir: string | null) {
+    const [reports, setReports] = useState<InstanceReport[] | null>(null);
+    // Issue kinds picked for repair, by instance directory
+    const [selected, setSelected] = useState<Record<string, IssueKind[]>>({});
+    const [checking, setChecking] = useState(false);
+    const [repairing, setRepairing] = useState<string | null>(null);
+    const [error, setError] = useState<string | null>(null);
+
+    // Reports of another PrismLauncher don't apply anymore
+    useEffect(() => {
+        setReports(null);
+        setSelected({});
+        setError(null);
+    }, [dataDir]);
+
+    // Check every installed instance of the modpack, all issues start out selected
+    const verify = async () => {
+        setChecking(true);
+        setError(null);
+        try {
+            const found = await invoke<InstanceReport[]>("verify_instance", { dataDir });
+            setReports(found);
+            setSelected(Object.fromEntries(found.map((report) => [
+                report.instance,
+                [...new Set(report.issues.map((issue) => issue.kind))]
+            ])));
+        } catch (error) {
+            console.error("Verification failed:", error);
+            setError(`${error}`);
+        } finally {
+            setChecking(false);
+        }
+    };
+
+    // Issues of one kind are repaired together, so they're selected together too
+    const toggleKind = (instance: string, kind: IssueKind) => {
+        setSelected((current) => {
+            const kinds = current[instance] ?? [];
+            return {
+                ...current,
+                [instance]: kinds.includes(kind) ? kinds.filter((k) => k !== kind) : [...kinds, kind]
+            };
+        });
+    };
+
+    // Repair the selected kinds of issues of one instance and show what's left afterwards
+    const repair = async (instance: string) => {
+        setRepairing(instance);
+        setError(null);
+        try {
+            const repaired = await invoke<InstanceReport[]>("repair_instance", {
+                dataDir,
+                instance,
+                kinds: selected[instance] ?? []
+            });
+            setReports((current) => (current ?? []).map((report) =>
+                repaired.find((r) => r.instance === report.instance) ?? report
+            ));
+            setSelected((current) => ({ ...current, [instance]: [] }));
+        } catch (error) {
+            console.error("Repair failed:", error);
+            setError(`${error}`);
+        } finally {
+            setRepairing(null);
+        }
+    };
+
+    return {
+        reports,
+        selected,
+        checking,
+        repairing,
+        error,
+        verify,
+        toggleKind,
+        repair
+    };
+}
@@ -11,6 +11,27 @@ export interface InstallProgressMessage {
   message: string;
 }
 
+export interface PhaseStatus {
+  phase: string;
+  label: string;
+  weight: number;
+  progress: number;
+  done: boolean;
+}
+
+// Payload of the `install_progress` event
+export interface InstallProgressEvent {
+  phase: string;
+  message: string;
+  overall: number;
+  phase_progress: number;
+  bytes_done: number | null;
+  bytes_total: number | null;
+  speed: number | null;
+  eta: number | null;
+  phases: PhaseStatus[];
+}
+
 export interface ModLoader {
   type: string;
   version: string;
@@ -25,4 +46,25 @@ export interface ModpackConfig {
   modloader: ModLoader;
   theme: 'light' | 'dark';
   background: string;
-}
\ No newline at end of file
+}
+
+// Issue kinds `verify_instance` reports and `repair_instance` can fix
+export type IssueKind =
+  | "missing_bootstrap"
+  | "modified_bootstrap"
+  | "pre_launch_command"
+  | "override_commands"
+  | "missing_icon"
+  | "missing_shortcut"
+  | "broken_shortcut";
+
+export interface Issue {
+  kind: IssueKind;
+  message: string;
+  path: string | null;
+}
+
+export interface InstanceReport {
+  instance: string;
+  issues: Issue[];
+}
@@ -198,15 +198,15 @@ const ModpackForm: React.FC<ModpackFormProps> = ({ onSubmit, isLoading, processi
 
                 <div>
                     <label htmlFor="base_pack_url" className="block text-sm font-medium mb-1">
-                        Base Pack URL
+                        Base Pack URL (optional)
                     </label>
                     <Input
                         id="base_pack_url"
                         name="base_pack_url"
-                        isRequired
                         value={formData.base_pack_url}
                         onChange={handleInputChange}
                         placeholder="https://example.com/basepack"
+                        description="Leave empty to create the instance from the Minecraft and loader versions in pack.toml"
                         className="w-full"
                     />
                 </div>
@@ -112,7 +112,7 @@ export default function IndexPage() {
       const jsonData = JSON.stringify(config);
 
       // Append JSON data to the executable - client-side operation
-      const resultBlob = appendDataToExecutable(executableArrayBuffer, jsonData);
+      const resultBlob = await appendDataToExecutable(executableArrayBuffer, jsonData);
 
       // Create a download URL
       const downloadUrl = URL.createObjectURL(resultBlob);
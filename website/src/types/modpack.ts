@@ -5,9 +5,13 @@ export interface ModpackConfig {
   description: string;
   logo_url: string;
   packwiz_url: string;
-  base_pack_url: string;
+  base_pack_url?: string; // Without it the instance is generated from the pack.toml at packwiz_url
   theme: 'dark' | 'light';
   background: string; // This field is named 'background' in the API but refers to a Minecraft block
+  base_pack_sha256?: string;
+  launcher_version?: string; // Semver requirement, e.g. "^9.4" or "=9.4.0"
+  launcher_fallback_versions?: string[];
+  launcher_sha256?: Record<string, string>; // Keyed by PrismLauncher release asset name
 }
 
 // GitHub Actions workflow interface
@@ -1,6 +1,7 @@
 // Utility functions for GitHub API interactions
 
 import { GitHubArtifactsResponse, GitHubWorkflowResponse } from '@/types/modpack';
+import { embedMetadata } from '@/utils/peMetadata';
 
 // GitHub repository used for API calls
 const GITHUB_REPO = 'sshcrack/packwiz-launcher';
@@ -122,46 +123,17 @@ export async function downloadFileBlob(url: string): Promise<Blob> {
 }
 
 /**
- * Append JSON data to an executable file
- * 
- * This function follows the requirements:
- * 1. Encode the data as JSON
- * 2. Translate it to bytes UTF-8
- * 3. Append it at the end of the executable
- * 4. Append the size of the byte array as u64
+ * Embed JSON data into an executable file
+ *
+ * Uses the trailer layout read by the installer, see `embedMetadata`. Signed installers keep a
+ * valid signature because the trailer goes into their signature instead of the end of the file.
  */
-export function appendDataToExecutable(
+export async function appendDataToExecutable(
   executableBuffer: ArrayBuffer,
   jsonData: string
-): Blob {
-  // Convert JSON to UTF-8 bytes
-  const encoder = new TextEncoder();
-  const jsonBytes = encoder.encode(jsonData);
-
-  // Create a Uint8Array to hold the executable + JSON + size
-  const executableArray = new Uint8Array(executableBuffer);
-
-  // Create a buffer for the size (8 bytes for u64)
-  const sizeBuffer = new ArrayBuffer(8);
-  const sizeView = new DataView(sizeBuffer);
-  sizeView.setBigUint64(0, BigInt(jsonBytes.length), true); // true for little-endian
-
-  // Combine the executable, JSON bytes, and size
-  const resultArray = new Uint8Array(
-    executableArray.length + jsonBytes.length + 8
-  );
-
-  // Copy the executable
-  resultArray.set(executableArray, 0);
-
-  // Copy the JSON data
-  resultArray.set(jsonBytes, executableArray.length);
-
-  // Copy the size (8 bytes for u64)
-  resultArray.set(
-    new Uint8Array(sizeBuffer),
-    executableArray.length + jsonBytes.length
-  );
+): Promise<Blob> {
+  const jsonBytes = new TextEncoder().encode(jsonData);
+  const resultArray = await embedMetadata(new Uint8Array(executableBuffer), jsonBytes);
 
   // Return as a Blob for easy downloading
   return new Blob([resultArray], { type: 'application/octet-stream' });
@@ -0,0 +1,233 @@
+// Embeds the modpack config into installers the same way as the `embed-metadata` tool
+
+const MAGIC = 'PWZLMETA';
+const FORMAT_VERSION = 1;
+
+// OID 2.25.269969011283810079518339104717726291198 of the unsigned signer attribute holding the
+// trailer of signed installers, must match SIGNATURE_ATTRIBUTE in metadata.rs
+const SIGNATURE_ATTRIBUTE = new Uint8Array([
+  0x69, 0x83, 0x96, 0x9a, 0x91, 0xfa, 0xbe, 0xfa, 0xea, 0x8c, 0x93, 0x9b, 0xe9, 0xcb, 0xce, 0xcb,
+  0xdb, 0xd0, 0xa9, 0x7e,
+]);
+// OID 1.2.840.113549.1.7.2 (signedData)
+const SIGNED_DATA = new Uint8Array([0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02]);
+
+const OID = 0x06;
+const OCTET_STRING = 0x04;
+const SEQUENCE = 0x30;
+const SET = 0x31;
+const CONTENT = 0xa0;
+const UNSIGNED_ATTRS = 0xa1;
+
+// Index of IMAGE_DIRECTORY_ENTRY_SECURITY in the optional header's data directories
+const SECURITY_DIRECTORY = 4;
+
+interface CertificateTable {
+  offset: number;
+  size: number;
+  // File offset of the security data directory entry pointing at the table
+  directoryEntry: number;
+}
+
+interface Tlv {
+  tag: number;
+  content: Uint8Array;
+  // The whole element including tag and length
+  raw: Uint8Array;
+}
+
+function concat(...parts: Uint8Array[]): Uint8Array {
+  const result = new Uint8Array(parts.reduce((len, part) => len + part.length, 0));
+  let offset = 0;
+  for (const part of parts) {
+    result.set(part, offset);
+    offset += part.length;
+  }
+  return result;
+}
+
+function equal(a: Uint8Array, b: Uint8Array): boolean {
+  return a.length === b.length && a.every((byte, i) => byte === b[i]);
+}
+
+/**
+ * Builds the trailer read by the installer:
+ * `[payload][sha256(payload)][payload len: u64 LE][version: u32 LE][PWZLMETA]`
+ */
+async function encodeTrailer(payload: Uint8Array): Promise<Uint8Array> {
+  const hash = new Uint8Array(await crypto.subtle.digest('SHA-256', payload));
+
+  const footer = new Uint8Array(8 + 4);
+  const view = new DataView(footer.buffer);
+  view.setBigUint64(0, BigInt(payload.length), true);
+  view.setUint32(8, FORMAT_VERSION, true);
+
+  return concat(payload, hash, footer, new TextEncoder().encode(MAGIC));
+}
+
+/**
+ * Finds the Authenticode certificate table of a PE image, null for unsigned or non-PE files
+ */
+function certificateTable(exe: Uint8Array): CertificateTable | null {
+  const view = new DataView(exe.buffer, exe.byteOffset, exe.byteLength);
+  if (exe.length < 0x40 || view.getUint16(0, true) !== 0x5a4d) {
+    return null;
+  }
+
+  const peHeader = view.getUint32(0x3c, true);
+  if (peHeader + 24 > exe.length || view.getUint32(peHeader, true) !== 0x00004550) {
+    return null;
+  }
+
+  // The optional header follows the 4 byte signature and the 20 byte COFF header
+  const optionalHeader = peHeader + 24;
+  if (optionalHeader + 2 > exe.length) {
+    return null;
+  }
+  const magic = view.getUint16(optionalHeader, true);
+  const [rvaCountOffset, directoriesOffset] = magic === 0x10b ? [92, 96] : magic === 0x20b ? [108, 112] : [0, 0];
+  if (directoriesOffset === 0 || optionalHeader + directoriesOffset + (SECURITY_DIRECTORY + 1) * 8 > exe.length) {
+    return null;
+  }
+  if (view.getUint32(optionalHeader + rvaCountOffset, true) <= SECURITY_DIRECTORY) {
+    return null;
+  }
+
+  // Unlike other data directories, the security directory holds a file offset instead of an RVA
+  const directoryEntry = optionalHeader + directoriesOffset + SECURITY_DIRECTORY * 8;
+  const offset = view.getUint32(directoryEntry, true);
+  const size = view.getUint32(directoryEntry + 4, true);
+  if (offset === 0 || size === 0 || offset + size > exe.length) {
+    return null;
+  }
+
+  return { offset, size, directoryEntry };
+}
+
+function next(data: Uint8Array): [Tlv, Uint8Array] {
+  if (data.length < 2 || (data[0] & 0x1f) === 0x1f) {
+    throw new Error('Invalid code signature: unsupported element');
+  }
+
+  let length = data[1];
+  let header = 2;
+  if (length >= 0x80) {
+    const count = length & 0x7f;
+    // 0x80 is BER's indefinite length, which signatures don't use
+    if (count === 0 || count > 4 || data.length < 2 + count) {
+      throw new Error('Invalid code signature: unsupported length');
+    }
+    length = 0;
+    for (let i = 0; i < count; i++) {
+      length = length * 256 + data[2 + i];
+    }
+    header += count;
+  }
+  if (data.length < header + length) {
+    throw new Error('Invalid code signature: truncated element');
+  }
+
+  const tlv = {
+    tag: data[0],
+    content: data.subarray(header, header + length),
+    raw: data.subarray(0, header + length),
+  };
+  return [tlv, data.subarray(header + length)];
+}
+
+function children(content: Uint8Array): Tlv[] {
+  const result: Tlv[] = [];
+  while (content.length > 0) {
+    const [child, rest] = next(content);
+    result.push(child);
+    content = rest;
+  }
+  return result;
+}
+
+function encode(tag: number, content: Uint8Array): Uint8Array {
+  if (content.length < 0x80) {
+    return concat(new Uint8Array([tag, content.length]), content);
+  }
+
+  const length: number[] = [];
+  for (let len = content.length; len > 0; len = Math.floor(len / 256)) {
+    length.unshift(len % 256);
+  }
+  return concat(new Uint8Array([tag, 0x80 | length.length, ...length]), content);
+}
+
+// Encodes `items` under `tag`, swapping the child at `index` for `replacement`
+function replace(tag: number, items: Tlv[], index: number, replacement: Uint8Array): Uint8Array {
+  return encode(tag, concat(...items.map((item, i) => (i === index ? replacement : item.raw))));
+}
+
+/**
+ * Sets the unsigned attribute holding the trailer on the first signer of a PKCS#7 signature
+ *
+ * Unsigned attributes aren't covered by the signature, so it stays valid. Other attributes like
+ * timestamps are kept as they are.
+ */
+function setSignatureTrailer(der: Uint8Array, trailer: Uint8Array): Uint8Array {
+  const contentInfo = children(next(der)[0].content);
+  if (contentInfo.length !== 2 || contentInfo[0].tag !== OID || !equal(contentInfo[0].content, SIGNED_DATA)) {
+    throw new Error('Invalid code signature: not a PKCS#7 SignedData');
+  }
+
+  const signedData = children(next(contentInfo[1].content)[0].content);
+  const signerInfos = children(signedData[signedData.length - 1].content);
+  if (signerInfos.length === 0) {
+    throw new Error('Invalid code signature: missing signer info');
+  }
+  const signerInfo = children(signerInfos[0].content);
+
+  const unsigned = signerInfo.find((item) => item.tag === UNSIGNED_ATTRS);
+  const attributes = (unsigned ? children(unsigned.content) : []).filter(
+    (attribute) => !equal(children(attribute.content)[0]?.content ?? new Uint8Array(), SIGNATURE_ATTRIBUTE)
+  );
+  const value = encode(SET, encode(OCTET_STRING, trailer));
+  const ours = encode(SEQUENCE, concat(encode(OID, SIGNATURE_ATTRIBUTE), value));
+  const attrs = encode(UNSIGNED_ATTRS, concat(...attributes.map((a) => a.raw), ours));
+
+  const newSignerInfo = encode(
+    SEQUENCE,
+    concat(...signerInfo.filter((item) => item.tag !== UNSIGNED_ATTRS).map((item) => item.raw), attrs)
+  );
+  const newSignerInfos = replace(SET, signerInfos, 0, newSignerInfo);
+  const newSignedData = replace(SEQUENCE, signedData, signedData.length - 1, newSignerInfos);
+  return replace(SEQUENCE, contentInfo, 1, encode(CONTENT, newSignedData));
+}
+
+/**
+ * Embeds the config into an installer
+ *
+ * Signed installers get the trailer in an unsigned attribute of their Authenticode signature so
+ * the signature stays valid, unsigned ones get it appended.
+ */
+export async function embedMetadata(exe: Uint8Array, payload: Uint8Array): Promise<Uint8Array> {
+  const trailer = await encodeTrailer(payload);
+
+  const table = certificateTable(exe);
+  if (!table || table.offset + table.size !== exe.length) {
+    return concat(exe, trailer);
+  }
+
+  const view = new DataView(exe.buffer, exe.byteOffset, exe.byteLength);
+  const entryLength = view.getUint32(table.offset, true);
+  if (entryLength < 8 || entryLength > table.size) {
+    throw new Error('Invalid WIN_CERTIFICATE length');
+  }
+
+  const signature = setSignatureTrailer(exe.subarray(table.offset + 8, table.offset + entryLength), trailer);
+  const rest = exe.subarray(Math.min(table.offset + Math.ceil(entryLength / 8) * 8, exe.length));
+  const padding = new Uint8Array((8 - ((8 + signature.length) % 8)) % 8);
+
+  const header = new Uint8Array(8);
+  new DataView(header.buffer).setUint32(0, 8 + signature.length, true);
+  // Keep wRevision and wCertificateType
+  header.set(exe.subarray(table.offset + 4, table.offset + 8), 4);
+
+  const result = concat(exe.subarray(0, table.offset), header, signature, padding, rest);
+  new DataView(result.buffer).setUint32(table.directoryEntry + 4, result.length - table.offset, true);
+  return result;
+}